@@ -0,0 +1,121 @@
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::store::{MapFile, Store};
+#[cfg(feature = "tar")]
+use crate::tar::Compression;
+use crate::File;
+
+/// Archive format, detected from the contents of the archive.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Format {
+    Zip,
+    #[cfg(feature = "tar")]
+    Tar(Compression),
+}
+
+impl Format {
+    // Enough bytes to reach the magic of a raw tar archive.
+    const MAGIC_LEN: u64 = 262;
+    // Size of the end of central directory record of a zip archive, followed
+    // by the longest possible comment.
+    const ZIP_TAIL_LEN: u64 = 22 + 0xffff;
+
+    fn detect<T: Read + Seek>(inner: &mut T) -> io::Result<Self> {
+        let magic = read_magic(inner)?;
+        // local file header, or the end of the central directory of an empty
+        // archive.
+        if magic.starts_with(b"PK\x03\x04") || magic.starts_with(b"PK\x05\x06") {
+            return Ok(Format::Zip);
+        }
+        #[cfg(feature = "tar")]
+        {
+            let compression = Compression::detect(&magic);
+            let header = if compression == Compression::None {
+                magic
+            } else {
+                // streams that don't decompress are not tar archives, but
+                // the codec must be enabled to tell.
+                inner.seek(SeekFrom::Start(0))?;
+                match read_magic(&mut compression.decoder(&mut *inner)?) {
+                    Ok(header) => header,
+                    Err(e) if e.kind() == io::ErrorKind::Unsupported => return Err(e),
+                    Err(_) => Vec::new(),
+                }
+            };
+            if header.get(257..262) == Some(b"ustar") {
+                return Ok(Format::Tar(compression));
+            }
+        }
+        // zip archives with data before them (such as self-extracting
+        // archives) are found from their end.
+        let len = inner.seek(SeekFrom::End(0))?;
+        inner.seek(SeekFrom::Start(len.saturating_sub(Self::ZIP_TAIL_LEN)))?;
+        let mut tail = Vec::new();
+        inner.read_to_end(&mut tail)?;
+        if tail.windows(4).any(|w| w == b"PK\x05\x06") {
+            return Ok(Format::Zip);
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unrecognized archive format",
+        ))
+    }
+}
+
+// Reads the first bytes of a stream, or all of it if it's shorter.
+fn read_magic<R: Read + ?Sized>(read: &mut R) -> io::Result<Vec<u8>> {
+    let mut magic = Vec::with_capacity(Format::MAGIC_LEN as usize);
+    read.take(Format::MAGIC_LEN).read_to_end(&mut magic)?;
+    Ok(magic)
+}
+
+/// Opens an archive from the native filesystem, whatever its format.
+///
+/// See [`read_archive`](./fn.read_archive.html).
+pub fn open_archive<P: AsRef<Path>>(
+    path: P,
+) -> io::Result<Box<dyn Store<File = File> + Send + Sync>> {
+    read_archive(fs::File::open(path)?)
+}
+
+/// Reads an archive, picking the store from the magic bytes at its start.
+///
+/// Zip archives are read with [`ZipFs`], and tar archives (raw or compressed)
+/// with [`TarFs`]. Formats whose cargo feature is disabled, and anything that
+/// is not an archive, are rejected with an error.
+///
+/// Tar archives are recognized by the `ustar` magic of their first header
+/// (after decompression), which archives from very old versions of tar lack.
+/// Zip archives with data before them, such as self-extracting archives, are
+/// recognized by the record at their end.
+///
+/// [`ZipFs`]: ./struct.ZipFs.html
+/// [`TarFs`]: ./struct.TarFs.html
+pub fn read_archive<T>(mut inner: T) -> io::Result<Box<dyn Store<File = File> + Send + Sync>>
+where
+    T: Read + Seek + Send + 'static,
+{
+    inner.seek(SeekFrom::Start(0))?;
+    let format = Format::detect(&mut inner)?;
+    inner.seek(SeekFrom::Start(0))?;
+
+    match format {
+        #[cfg(feature = "zip")]
+        Format::Zip => {
+            let zip = crate::ZipFs::new(inner);
+            Ok(Box::new(MapFile::new(zip, File::from)))
+        }
+        #[cfg(not(feature = "zip"))]
+        Format::Zip => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "zip support is not enabled",
+        )),
+        #[cfg(feature = "tar")]
+        Format::Tar(compression) => {
+            let tar = crate::TarFs::new(inner).compression(compression);
+            Ok(Box::new(MapFile::new(tar, File::from)))
+        }
+    }
+}
@@ -14,20 +14,143 @@
 //! components are compared individually. Path components with valid utf8 are
 //! compared in a case-insensitive way. Path components with invalid utf8 are
 //! compared raw (case-sensitive).
+//!
+//! By default, names are compared with full Unicode case folding, so `Ä.png`
+//! matches `ä.png` and `STRASSE` matches `straße`. ASCII case folding is
+//! faster, and Turkic case folding matches `İ` to `i` and `I` to `ı`. Both can
+//! be selected with [`CaselessFs::folding`]. Names can also be
+//! normalized before the comparison with [`CaselessFs::normalization`], so the
+//! decomposed names (NFD) used by macOS match composed ones (NFC).
+//!
+//! Directories are listed caselessly too. Directories whose paths only differ
+//! in case are merged into a single listing.
+//!
+//! When several real paths match a caseless path, the one listed first by the
+//! inner filesystem is used, unless the caseless filesystem is
+//! [strict](./struct.CaselessFs.html#method.strict). [`CaselessFs::collisions`]
+//! finds the names that match each other in a whole tree.
+//!
+//! Looking up a caseless path lists every directory along the path. With
+//! [`CaselessFs::cached`], the folded names of each listed directory are kept
+//! in a lookup table, so later lookups don't list the directory again.
+//!
+//! [`CaselessFs::folding`]: ./struct.CaselessFs.html#method.folding
+//! [`CaselessFs::normalization`]: ./struct.CaselessFs.html#method.normalization
+//! [`CaselessFs::cached`]: ./struct.CaselessFs.html#method.cached
+//! [`CaselessFs::collisions`]: ./struct.CaselessFs.html#method.collisions
 
-use std::ffi::OsString;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
 use std::io;
 use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use unicode_normalization::UnicodeNormalization;
 
 use crate::index::normalize_path;
 use crate::prelude::*;
-use crate::store::Entries;
+use crate::store::{Entries, EntryKind, Metadata};
+
+/// Case folding used to compare names.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Folding {
+    /// Only ASCII letters are compared case-insensitively.
+    Ascii,
+    /// Full Unicode case folding.
+    ///
+    /// The dotted and dotless `i` of Turkish and Azerbaijani are not matched
+    /// to their uppercase forms, use `Folding::Turkic` for that.
+    #[default]
+    Unicode,
+    /// Full Unicode case folding, with the Turkic mappings for `i`: `I`
+    /// matches `ı`, and `İ` matches `i`.
+    Turkic,
+}
+
+/// Unicode normalization applied to names before comparing them.
+///
+/// Both forms make canonically equivalent names match. They only differ in the
+/// form of the names reported by the caseless filesystem.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Normalization {
+    /// Names are compared as they are.
+    #[default]
+    None,
+    /// Canonical composition (NFC).
+    Nfc,
+    /// Canonical decomposition (NFD).
+    Nfd,
+}
+
+/// Error of a strict caseless filesystem when a caseless path matches more than
+/// one real path.
+///
+/// It is returned wrapped in an `io::Error` of kind `ErrorKind::Other`, and
+/// can be recovered with `get_ref` and `downcast_ref`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Ambiguous {
+    /// Caseless path that was looked up.
+    pub path: PathBuf,
+    /// Real paths that match it, in the order of the inner filesystem.
+    pub candidates: Vec<PathBuf>,
+}
+
+impl fmt::Display for Ambiguous {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} is ambiguous, it matches", self.path.display())?;
+        for (i, candidate) in self.candidates.iter().enumerate() {
+            let sep = if i == 0 { " " } else { ", " };
+            write!(f, "{}{}", sep, candidate.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for Ambiguous {}
+
+/// Real names of the entries of a directory, by their folded name.
+type Table = HashMap<OsString, Vec<OsString>>;
 
 /// Caseless filesystem wrapping an inner filesystem.
-#[derive(Clone, Debug)]
 pub struct CaselessFs<S> {
     /// Inner filesystem store.
     inner: S,
+    /// Case folding used to compare names.
+    folding: Folding,
+    /// Normalization applied to names before comparing them.
+    normalization: Normalization,
+    /// Lookup tables of the listed directories, by their real path.
+    cache: Option<RwLock<HashMap<PathBuf, Arc<Table>>>>,
+    /// Fail on caseless paths that match more than one real path.
+    strict: bool,
+}
+
+impl<S: Clone> Clone for CaselessFs<S> {
+    /// Clones the caseless filesystem. The clone starts with an empty cache.
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            folding: self.folding,
+            normalization: self.normalization,
+            cache: self.cache.as_ref().map(|_| RwLock::default()),
+            strict: self.strict,
+        }
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for CaselessFs<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CaselessFs")
+            .field("inner", &self.inner)
+            .field("folding", &self.folding)
+            .field("normalization", &self.normalization)
+            .field("cached", &self.cache.is_some())
+            .field("strict", &self.strict)
+            .finish()
+    }
 }
 
 impl<S: Store> CaselessFs<S> {
@@ -35,7 +158,117 @@ impl<S: Store> CaselessFs<S> {
     /// It treats paths as case-insensitive, regardless of the case of the inner
     /// filesystem.
     pub fn new(inner: S) -> Self {
-        Self { inner }
+        Self {
+            inner,
+            folding: Folding::default(),
+            normalization: Normalization::default(),
+            cache: None,
+            strict: false,
+        }
+    }
+
+    /// Sets the case folding used to compare names.
+    /// Defaults to `Folding::Unicode`.
+    pub fn folding(mut self, folding: Folding) -> Self {
+        self.folding = folding;
+        self.invalidate_all();
+        self
+    }
+
+    /// Sets the normalization applied to names before comparing them.
+    /// Defaults to `Normalization::None`.
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = normalization;
+        self.invalidate_all();
+        self
+    }
+
+    /// Fails with an [`Ambiguous`] error when a caseless path matches more than
+    /// one real path, instead of using the first one.
+    ///
+    /// Paths that match a real path exactly are never ambiguous.
+    ///
+    /// [`Ambiguous`]: ./struct.Ambiguous.html
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    /// Caches the lookup table of each directory the first time it is listed.
+    ///
+    /// Changes to the inner filesystem made through [`get_mut`] clear the
+    /// cache. Other changes (files created in a `LocalFs` by another program,
+    /// for example) aren't noticed until the affected directories are
+    /// [invalidated].
+    ///
+    /// [`get_mut`]: #method.get_mut
+    /// [invalidated]: #method.invalidate
+    pub fn cached(mut self) -> Self {
+        self.cache = Some(RwLock::default());
+        self
+    }
+
+    /// Caches the lookup tables of every directory of the inner filesystem
+    /// upfront, instead of the first time each one is listed. Indexed
+    /// archives list their directories without reading the archive, so this
+    /// is cheap for them.
+    pub fn index(self) -> io::Result<Self> {
+        let fs = if self.cache.is_some() {
+            self
+        } else {
+            self.cached()
+        };
+        fs.table(Path::new(""))?;
+        for next in fs.inner.walk("") {
+            let (path, entry) = next?;
+            if entry.kind == EntryKind::Dir {
+                fs.table(&path)?;
+            }
+        }
+        Ok(fs)
+    }
+
+    /// Finds the entries of the directories under `path` whose names only
+    /// differ in case. Returns the real paths of each group of colliding
+    /// entries.
+    pub fn collisions<P: AsRef<Path>>(&self, path: P) -> io::Result<Vec<Vec<PathBuf>>> {
+        let path = normalize_path(path.as_ref());
+        let mut dirs = vec![path.to_path_buf()];
+        for next in self.inner.walk(&path) {
+            let (path, entry) = next?;
+            if entry.kind == EntryKind::Dir {
+                dirs.push(path);
+            }
+        }
+        let mut collisions = Vec::new();
+        for dir in dirs {
+            for names in self.table(&dir)?.values().filter(|n| n.len() > 1) {
+                let mut paths = names.iter().map(|n| dir.join(n)).collect::<Vec<_>>();
+                paths.sort();
+                collisions.push(paths);
+            }
+        }
+        collisions.sort();
+        Ok(collisions)
+    }
+
+    /// Drops the cached lookup tables of a directory (given by its real path)
+    /// and of all the directories below it.
+    pub fn invalidate<P: AsRef<Path>>(&self, path: P) {
+        let path = normalize_path(path.as_ref());
+        if let Some(ref cache) = self.cache {
+            cache
+                .write()
+                .unwrap()
+                .retain(|dir, _| !dir.starts_with(&path));
+        }
+    }
+
+    /// Drops all the cached lookup tables.
+    pub fn invalidate_all(&self) {
+        if let Some(ref cache) = self.cache {
+            cache.write().unwrap().clear();
+        }
     }
 
     /// Moves the inner filesystem out of the caseless filesystem.
@@ -52,7 +285,11 @@ impl<S: Store> CaselessFs<S> {
 
     /// Gets a mutable reference to the inner filesystem.
     /// Inspired by std::io::Cursor.
+    ///
+    /// Clears the cached lookup tables, since the inner filesystem might
+    /// change.
     pub fn get_mut(&mut self) -> &mut S {
+        self.invalidate_all();
         &mut self.inner
     }
 
@@ -63,13 +300,111 @@ impl<S: Store> CaselessFs<S> {
         let path = normalize_path(path.as_ref());
         let mut paths = vec![PathBuf::new()];
         for component in path.components() {
-            paths = find_next_ascii_lowercase(&self.inner, &component, paths);
-            if paths.len() == 0 {
+            paths = self.find_next(&component, paths);
+            if paths.is_empty() {
                 return paths;
             }
         }
         paths
     }
+
+    /// Resolves a caseless path into the real path it matches.
+    fn resolve(&self, path: &Path) -> io::Result<PathBuf> {
+        let mut candidates = self.find(path);
+        match candidates.len() {
+            0 => Err(io::ErrorKind::NotFound.into()),
+            1 => Ok(candidates.remove(0)),
+            _ if self.strict => Err(io::Error::other(Ambiguous {
+                path: path.to_path_buf(),
+                candidates,
+            })),
+            _ => Ok(candidates.remove(0)),
+        }
+    }
+
+    /// Folds a name into the key used to compare it.
+    /// Names with invalid utf8 are returned raw.
+    fn fold<'n>(&self, name: &'n OsStr) -> Cow<'n, OsStr> {
+        let name = match name.to_str() {
+            Some(name) => name,
+            None => return Cow::Borrowed(name),
+        };
+        let turkic;
+        let name = if self.folding == Folding::Turkic {
+            turkic = name
+                .chars()
+                .map(|c| match c {
+                    'I' => 'ı',
+                    'İ' => 'i',
+                    c => c,
+                })
+                .collect::<String>();
+            turkic.as_str()
+        } else {
+            name
+        };
+        let folded = match (self.folding, self.normalization) {
+            (Folding::Ascii, _) => name.to_ascii_lowercase(),
+            (_, Normalization::None) => caseless::default_case_fold_str(name),
+            // canonical caseless matching folds the decomposed name
+            (_, _) => caseless::default_case_fold_str(&name.nfd().collect::<String>()),
+        };
+        let folded = match self.normalization {
+            Normalization::None => folded,
+            Normalization::Nfc => folded.nfc().collect(),
+            Normalization::Nfd => folded.nfd().collect(),
+        };
+        Cow::Owned(OsString::from(folded))
+    }
+
+    /// Finds the next path candidates.
+    fn find_next(&self, component: &Component, paths: Vec<PathBuf>) -> Vec<PathBuf> {
+        let mut next = Vec::new();
+        let target = match component {
+            Component::Normal(os_s) => self.fold(os_s),
+            Component::RootDir => {
+                // nothing can go before the root
+                next.push(Path::new("/").to_owned());
+                return next;
+            }
+            _ => {
+                panic!("unexpected path component {:?}", component);
+            }
+        };
+        for path in paths {
+            if let Ok(table) = self.table(&path) {
+                for name in table.get(&*target).into_iter().flatten() {
+                    next.push(path.join(name));
+                }
+            }
+        }
+        next
+    }
+
+    /// Returns the lookup table of a directory, listing it if it isn't cached.
+    fn table(&self, path: &Path) -> io::Result<Arc<Table>> {
+        if let Some(ref cache) = self.cache {
+            if let Some(table) = cache.read().unwrap().get(path) {
+                return Ok(Arc::clone(table));
+            }
+        }
+        let mut table = Table::new();
+        for entry in self.inner.entries(path)?.flatten() {
+            let name = crate::walk::file_name(&entry);
+            table
+                .entry(self.fold(name).into_owned())
+                .or_default()
+                .push(name.to_os_string());
+        }
+        let table = Arc::new(table);
+        if let Some(ref cache) = self.cache {
+            cache
+                .write()
+                .unwrap()
+                .insert(path.to_path_buf(), Arc::clone(&table));
+        }
+        Ok(table)
+    }
 }
 
 impl<S: Store> Store for CaselessFs<S> {
@@ -78,75 +413,89 @@ impl<S: Store> Store for CaselessFs<S> {
     /// Opens the file identified by the caseless path.
     /// A caseless path that matches the real path of a file always opens that
     /// file. Otherwise a caseless path will open the first path of the
-    /// inner filesystem that matches the caseless path.
+    /// inner filesystem that matches the caseless path, or fail if there are
+    /// several and the caseless filesystem is strict.
     fn open_path(&self, path: &Path) -> io::Result<Self::File> {
         // real path
         if let Ok(file) = self.inner.open_path(path) {
             return Ok(file);
         }
         // caseless path
-        for path in self.find(path) {
-            return self.inner.open_path(&path);
-        }
-        Err(io::ErrorKind::NotFound.into())
+        self.inner.open_path(&self.resolve(path)?)
     }
 
-    /// Iterates over the entries of the inner filesystem.
-    fn entries_path(&self, path: &Path) -> io::Result<Entries> {
-        self.inner.entries_path(path)
-    }
-}
-
-/// Finds the next path candidates.
-fn find_next_ascii_lowercase<S: Store>(
-    fs: &S,
-    component: &Component,
-    paths: Vec<PathBuf>,
-) -> Vec<PathBuf> {
-    let mut next = Vec::new();
-    let target: OsString = match component {
-        Component::Normal(os_s) => (*os_s).to_owned(),
-        Component::RootDir => {
-            // nothing can go before the root
-            next.push(Path::new("/").to_owned());
-            return next;
-        }
-        _ => {
-            panic!("unexpected path component {:?}", component);
-        }
-    };
-    if let Some(t_s) = target.to_str() {
-        // compare utf8
-        for path in paths {
-            if let Ok(entries) = fs.entries(&path) {
-                for e in entries {
-                    if let Ok(entry) = e {
-                        if let Some(e_s) = entry.name.to_str() {
-                            if t_s.to_ascii_lowercase() == e_s.to_ascii_lowercase() {
-                                let mut path = path.to_owned();
-                                path.push(&entry.name);
-                                next.push(path);
-                            }
-                        }
-                    }
-                }
-            }
+    /// Iterates over the entries of the directories identified by the caseless
+    /// path. Directories whose paths only differ in case are merged into one
+    /// listing, starting with the one that matches the real path, if any.
+    /// Entries of a later directory are skipped when an earlier one has an
+    /// entry whose name only differs in case. Entries of the same directory
+    /// are all listed, like the [collisions](#method.collisions) they are.
+    fn entries_path(&self, path: &Path) -> io::Result<Entries<'_>> {
+        // the ancestors are looked up in the lookup tables, so with a cache
+        // only the directory itself is listed
+        let path = normalize_path(path);
+        let mut dirs = self.find(&path);
+        // without case variants, the real listing is used as is
+        if dirs.is_empty() || dirs.len() == 1 && dirs[0] == path {
+            return self.inner.entries_path(&path);
         }
-    } else {
-        // compare raw
-        for path in paths {
-            if let Ok(entries) = fs.entries(&path) {
-                for e in entries {
-                    if let Ok(entry) = e {
-                        if &entry.name == &target {
-                            let mut path = path.to_owned();
-                            path.push(&entry.name);
-                            next.push(path);
-                        }
+        // the real path goes first, even if it can't be found by listing its
+        // parents
+        dirs.retain(|dir| *dir != path);
+        dirs.insert(0, path.into_owned());
+        let mut error = None;
+        let mut listed = false;
+        let mut seen = HashSet::new();
+        let mut entries = Vec::new();
+        for dir in dirs {
+            let listing = match self.inner.entries_path(&dir) {
+                Ok(listing) => listing,
+                Err(e) => {
+                    // might be a file with the same caseless name
+                    error.get_or_insert(e);
+                    continue;
+                }
+            };
+            listed = true;
+            let mut names = Vec::new();
+            for entry in listing {
+                if let Ok(ref entry) = entry {
+                    let name = self.fold(crate::walk::file_name(entry)).into_owned();
+                    if seen.contains(&name) {
+                        continue;
                     }
+                    names.push(name);
                 }
+                entries.push(entry);
             }
+            // only the directories listed before hide entries
+            seen.extend(names);
         }
+        match error {
+            Some(e) if !listed => Err(e),
+            _ => Ok(Entries::new(entries)),
+        }
+    }
+
+    /// Returns the metadata of the file identified by the caseless path.
+    /// Candidates are chosen the same way as in `open_path`.
+    fn metadata_path(&self, path: &Path) -> io::Result<Metadata> {
+        // real path
+        if let Ok(meta) = self.inner.metadata_path(path) {
+            return Ok(meta);
+        }
+        // caseless path
+        self.inner.metadata_path(&self.resolve(path)?)
+    }
+
+    /// Reads the symbolic link identified by the caseless path.
+    /// Candidates are chosen the same way as in `open_path`.
+    fn read_link_path(&self, path: &Path) -> io::Result<PathBuf> {
+        // real path
+        if let Ok(target) = self.inner.read_link_path(path) {
+            return Ok(target);
+        }
+        // caseless path
+        self.inner.read_link_path(&self.resolve(path)?)
     }
-    next
 }
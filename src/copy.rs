@@ -0,0 +1,177 @@
+//! Copies a directory tree from a store into a writable store.
+//!
+//! ```
+//! # fn main() -> std::io::Result<()> {
+//! use mini_fs::prelude::*;
+//! use mini_fs::{copy, RamFs};
+//!
+//! let mut src = RamFs::new();
+//! src.touch("dlc/levels/1.map", b"level 1".to_vec());
+//! src.touch("dlc/levels/2.map", b"level 2".to_vec());
+//!
+//! let dst = RamFs::new();
+//! let options = copy::Options::new().conflict(copy::Conflict::Overwrite);
+//! let progress = copy::copy_tree(&src, "dlc/levels", &dst, "levels", options)?;
+//!
+//! assert_eq!(2, progress.files);
+//! assert!(dst.open("levels/2.map").is_ok());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use crate::index::normalize_path;
+use crate::store::{EntryKind, Store, StoreMut};
+use crate::walk::Walk;
+
+type ProgressFn<'a> = Box<dyn FnMut(&Path, &Progress) + 'a>;
+
+/// What to do with files that already exist in the destination.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Conflict {
+    /// Stop the copy with an `AlreadyExists` error.
+    #[default]
+    Fail,
+    /// Leave the existing file as it is.
+    Skip,
+    /// Replace the existing file.
+    Overwrite,
+}
+
+/// Counters of a copy in progress, or of a finished one.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Progress {
+    /// Files copied so far.
+    pub files: u64,
+    /// Bytes copied so far.
+    pub bytes: u64,
+    /// Files skipped because they already existed.
+    pub skipped: u64,
+}
+
+/// Options of [`copy_tree`].
+///
+/// [`copy_tree`]: ./fn.copy_tree.html
+#[derive(Default)]
+pub struct Options<'a> {
+    conflict: Conflict,
+    progress: Option<ProgressFn<'a>>,
+}
+
+impl<'a> Options<'a> {
+    /// Creates the default options, which fail on existing files.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets what to do with files that already exist in the destination.
+    pub fn conflict(mut self, conflict: Conflict) -> Self {
+        self.conflict = conflict;
+        self
+    }
+
+    /// Calls `callback` after each file is copied or skipped, with its path in
+    /// the destination and the counters so far.
+    pub fn progress<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&Path, &Progress) + 'a,
+    {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+}
+
+/// Copies the files and directories under `src_path` in `src` into
+/// `dst_path` in `dst`, which is created if it doesn't exist.
+///
+/// Entries whose names would place them outside of `dst_path` (such as `..`)
+/// stop the copy with a `PermissionDenied` error. To also keep the symbolic
+/// links of a local destination from being followed out of it, copy into a
+/// [`LocalFs::sandboxed`] store.
+///
+/// Symbolic links are copied as regular files with the contents of their
+/// target, and links to directories are skipped.
+///
+/// Returns the counters of the finished copy.
+///
+/// [`LocalFs::sandboxed`]: ../struct.LocalFs.html#method.sandboxed
+pub fn copy_tree<S, D, P, Q>(
+    src: &S,
+    src_path: P,
+    dst: &D,
+    dst_path: Q,
+    mut options: Options,
+) -> io::Result<Progress>
+where
+    S: Store + ?Sized,
+    S::File: Read,
+    D: StoreMut + ?Sized,
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let src_path = normalize_path(src_path.as_ref());
+    let dst_path = normalize_path(dst_path.as_ref());
+    let mut progress = Progress::default();
+
+    dst.create_dir_all_path(&dst_path)?;
+    for next in Walk::new(src, src_path.to_path_buf()) {
+        let (path, entry) = next?;
+        let to = dst_path.join(sanitize(&src_path, &path)?);
+        let is_dir = match entry.kind {
+            EntryKind::Dir => true,
+            // the metadata of links describes their target
+            EntryKind::Symlink => src.metadata_path(&path).is_ok_and(|meta| meta.is_dir()),
+            EntryKind::File => false,
+        };
+        if is_dir {
+            if entry.kind == EntryKind::Dir {
+                dst.create_dir_all_path(&to)?;
+            }
+            continue;
+        }
+
+        if dst.metadata_path(&to).is_ok() {
+            match options.conflict {
+                Conflict::Fail => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{} already exists", to.display()),
+                    ))
+                }
+                Conflict::Skip => {
+                    progress.skipped += 1;
+                    if let Some(ref mut callback) = options.progress {
+                        callback(&to, &progress);
+                    }
+                    continue;
+                }
+                Conflict::Overwrite => {}
+            }
+        }
+        let mut file = src.open_path(&path)?;
+        let mut out = dst.create_path(&to)?;
+        progress.bytes += io::copy(&mut file, &mut out)?;
+        out.flush()?;
+        progress.files += 1;
+        if let Some(ref mut callback) = options.progress {
+            callback(&to, &progress);
+        }
+    }
+    Ok(progress)
+}
+
+// Returns the path of an entry relative to the copied directory, making sure
+// it stays inside of it.
+fn sanitize(root: &Path, path: &Path) -> io::Result<PathBuf> {
+    let rel = path.strip_prefix(root).unwrap_or(path);
+    if rel.components().all(|c| matches!(c, Component::Normal(_))) {
+        Ok(rel.to_path_buf())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "path escapes the destination",
+        ))
+    }
+}
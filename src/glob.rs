@@ -0,0 +1,432 @@
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::vec;
+
+use crate::index::{normalize_path, Index};
+use crate::store::{Entry, EntryKind, Store};
+use crate::walk::file_name;
+
+// Positions in the pattern that can match the next path component, as pairs of
+// (alternative, segment).
+pub(crate) type States = BTreeSet<(usize, usize)>;
+
+/// Compiled glob pattern.
+///
+/// Patterns are matched against paths one component at a time:
+///
+/// - `?` matches any single character.
+/// - `*` matches any sequence of characters within a component.
+/// - `**` (as a whole component) matches any number of components.
+/// - `[abc]`, `[a-z]` match any of the characters of the class, and `[!abc]`
+///   or `[^abc]` any character not in it.
+/// - `{a,b}` matches either of the alternatives. Alternatives can contain any
+///   of the above, including `/` and nested braces.
+/// - `\` escapes the character that follows it.
+///
+/// ```
+/// use mini_fs::Pattern;
+///
+/// let pattern = Pattern::new("/assets/**/*.{png,gif}")?;
+///
+/// assert!(pattern.matches("/assets/a.png"));
+/// assert!(pattern.matches("/assets/gfx/b.gif"));
+/// assert!(!pattern.matches("/assets/c.wav"));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    // Leading literal components, shared by every alternative.
+    base: PathBuf,
+    // Remaining components of each alternative of the brace expansion.
+    alternatives: Vec<Vec<Segment>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    // `**`
+    Recursive,
+    Name(Vec<Token>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Char(char),
+    Any,
+    Star,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Pattern {
+    /// Compiles a glob pattern.
+    ///
+    /// Returns an error of kind `ErrorKind::InvalidInput` if the pattern is
+    /// malformed.
+    pub fn new(pattern: &str) -> io::Result<Self> {
+        let mut alternatives = Vec::new();
+        for alternative in expand_braces(pattern)? {
+            let path = normalize_path(Path::new(&alternative)).into_owned();
+            let mut segments = Vec::new();
+            for comp in path.components() {
+                segments.push(match comp {
+                    Component::Normal(name) => parse_segment(&name.to_string_lossy())?,
+                    comp => Segment::Name(literal(comp.as_os_str())),
+                });
+            }
+            alternatives.push(segments);
+        }
+
+        // move the leading literal components (except the last one) to the base
+        let mut base = PathBuf::new();
+        let first = alternatives[0].clone();
+        for (i, segment) in first.iter().enumerate() {
+            let name = match segment {
+                Segment::Name(tokens) => literal_name(tokens),
+                Segment::Recursive => None,
+            };
+            let shared = alternatives
+                .iter()
+                .all(|alt| alt.len() > i + 1 && alt[i] == *segment);
+            match name {
+                Some(name) if shared => base.push(name),
+                _ => break,
+            }
+        }
+        let skip = base.components().count();
+        for alternative in &mut alternatives {
+            alternative.drain(..skip);
+        }
+        Ok(Self { base, alternatives })
+    }
+
+    /// Returns true if the path matches the pattern.
+    pub fn matches<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = normalize_path(path.as_ref());
+        let rest = match path.strip_prefix(&self.base) {
+            Ok(rest) => rest,
+            Err(_) => return false,
+        };
+        let mut states = self.start();
+        let mut matched = false;
+        for name in rest.iter() {
+            if states.is_empty() {
+                return false;
+            }
+            let (next, m) = self.step(&states, name);
+            states = next;
+            matched = m;
+        }
+        matched
+    }
+
+    /// Directory the matches of the pattern are under.
+    pub(crate) fn base(&self) -> &Path {
+        &self.base
+    }
+
+    /// States before matching the first component under the base.
+    pub(crate) fn start(&self) -> States {
+        self.closure((0..self.alternatives.len()).map(|a| (a, 0)).collect())
+    }
+
+    /// Matches a path component. Returns the states that can match the
+    /// components under it (empty if there is no point in descending into it),
+    /// and whether the path up to the component matches the pattern.
+    pub(crate) fn step(&self, states: &States, name: &OsStr) -> (States, bool) {
+        let name = name.to_string_lossy();
+        let mut next = States::new();
+        let mut matched = false;
+        for &(a, i) in states {
+            let segments = &self.alternatives[a];
+            match segments.get(i) {
+                Some(Segment::Recursive) => {
+                    // a trailing `**` matches everything under it
+                    matched |= segments[i + 1..].iter().all(|s| *s == Segment::Recursive);
+                    next.insert((a, i));
+                }
+                Some(Segment::Name(tokens)) if match_tokens(tokens, &name) => {
+                    matched |= i + 1 == segments.len();
+                    next.insert((a, i + 1));
+                }
+                _ => {}
+            }
+        }
+        let next = self
+            .closure(next)
+            .into_iter()
+            .filter(|&(a, i)| i < self.alternatives[a].len())
+            .collect();
+        (next, matched)
+    }
+
+    // Adds the states that skip `**` segments (which can match zero components).
+    fn closure(&self, mut states: States) -> States {
+        let mut stack = states.iter().cloned().collect::<Vec<_>>();
+        while let Some((a, i)) = stack.pop() {
+            if let Some(Segment::Recursive) = self.alternatives[a].get(i) {
+                if states.insert((a, i + 1)) {
+                    stack.push((a, i + 1));
+                }
+            }
+        }
+        states
+    }
+}
+
+/// Iterator over the files & directories that match a glob pattern.
+///
+/// Returned by [`StoreExt::glob`].
+///
+/// [`StoreExt::glob`]: ./trait.StoreExt.html#method.glob
+pub struct Glob<'a> {
+    inner: Box<dyn Iterator<Item = io::Result<(PathBuf, Entry)>> + 'a>,
+}
+
+impl<'a> Glob<'a> {
+    pub fn new<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = <Glob<'a> as Iterator>::Item>,
+        <I as IntoIterator>::IntoIter: 'a,
+    {
+        Self {
+            inner: Box::new(iter.into_iter()),
+        }
+    }
+
+    /// Matches of a pattern in an index.
+    pub(crate) fn index<M, F>(index: &Index<M>, pattern: &Pattern, kind: F) -> Glob<'static>
+    where
+        F: Fn(&M) -> EntryKind,
+    {
+        Glob::new(index.glob(pattern, kind).into_iter().map(Ok))
+    }
+
+    /// Matches the pattern by listing the directories of the store.
+    /// Directories that can't contain matches are not listed.
+    pub(crate) fn walk<S: Store + ?Sized>(store: &'a S, pattern: Pattern) -> Self {
+        let pending = Some((pattern.base().to_path_buf(), pattern.start()));
+        Self::new(Walk {
+            store,
+            pattern,
+            dirs: Vec::new(),
+            pending,
+        })
+    }
+}
+
+impl Iterator for Glob<'_> {
+    type Item = io::Result<(PathBuf, Entry)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+// Depth-first search of the matches of a pattern.
+struct Walk<'a, S: ?Sized> {
+    store: &'a S,
+    pattern: Pattern,
+    dirs: Vec<(PathBuf, States, vec::IntoIter<io::Result<Entry>>)>,
+    // Directory to descend into on the next call to next.
+    pending: Option<(PathBuf, States)>,
+}
+
+impl<S: Store + ?Sized> Iterator for Walk<'_, S> {
+    type Item = io::Result<(PathBuf, Entry)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((path, states)) = self.pending.take() {
+                match self.store.entries_path(&path) {
+                    Ok(entries) => {
+                        let entries = entries.collect::<Vec<_>>().into_iter();
+                        self.dirs.push((path, states, entries));
+                    }
+                    Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+
+            let (dir, states, entries) = self.dirs.last_mut()?;
+            let entry = match entries.next() {
+                Some(Ok(entry)) => entry,
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.dirs.pop();
+                    continue;
+                }
+            };
+            let name = file_name(&entry);
+            let (next, matched) = self.pattern.step(states, name);
+            let path = dir.join(name);
+            if entry.kind == EntryKind::Dir && !next.is_empty() {
+                self.pending = Some((path.clone(), next));
+            }
+            if matched {
+                return Some(Ok((path, entry)));
+            }
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+// Expands the brace alternatives of a pattern.
+fn expand_braces(pattern: &str) -> io::Result<Vec<String>> {
+    let chars = pattern.chars().collect::<Vec<_>>();
+    let mut open = None;
+    let mut depth = 0;
+    let mut commas = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '{' => {
+                if depth == 0 {
+                    open = Some(i);
+                }
+                depth += 1;
+            }
+            ',' if depth == 1 => commas.push(i),
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    let open = match open {
+        None => return Ok(vec![pattern.to_string()]),
+        Some(_) if depth > 0 => return Err(invalid("unclosed brace in glob pattern")),
+        Some(open) => open,
+    };
+
+    let prefix = chars[..open].iter().collect::<String>();
+    let suffix = chars[i + 1..].iter().collect::<String>();
+    let mut bounds = vec![open];
+    bounds.extend(commas);
+    bounds.push(i);
+
+    let mut expanded = Vec::new();
+    for pair in bounds.windows(2) {
+        let alternative = chars[pair[0] + 1..pair[1]].iter().collect::<String>();
+        expanded.extend(expand_braces(&format!(
+            "{}{}{}",
+            prefix, alternative, suffix
+        ))?);
+    }
+    Ok(expanded)
+}
+
+fn parse_segment(name: &str) -> io::Result<Segment> {
+    if name == "**" {
+        return Ok(Segment::Recursive);
+    }
+    let mut tokens = Vec::new();
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '?' => Token::Any,
+            '*' => {
+                if tokens.last() == Some(&Token::Star) {
+                    continue;
+                }
+                Token::Star
+            }
+            '\\' => match chars.next() {
+                Some(c) => Token::Char(c),
+                None => return Err(invalid("trailing escape in glob pattern")),
+            },
+            '[' => {
+                let negated = match chars.peek() {
+                    Some('!') | Some('^') => {
+                        chars.next();
+                        true
+                    }
+                    _ => false,
+                };
+                let mut ranges = Vec::new();
+                loop {
+                    let lo = match chars.next() {
+                        // `]` right after the opening bracket is a literal
+                        Some(']') if !ranges.is_empty() => break,
+                        Some('\\') => chars.next(),
+                        c => c,
+                    };
+                    let lo = lo.ok_or_else(|| invalid("unclosed class in glob pattern"))?;
+                    let mut ahead = chars.clone();
+                    let hi = match (ahead.next(), ahead.next()) {
+                        (Some('-'), Some(hi)) if hi != ']' => {
+                            chars.next();
+                            chars.next();
+                            hi
+                        }
+                        _ => lo,
+                    };
+                    ranges.push((lo, hi));
+                }
+                Token::Class { negated, ranges }
+            }
+            c => Token::Char(c),
+        });
+    }
+    Ok(Segment::Name(tokens))
+}
+
+fn literal(name: &OsStr) -> Vec<Token> {
+    name.to_string_lossy().chars().map(Token::Char).collect()
+}
+
+// Returns the name matched by a segment without wildcards.
+fn literal_name(tokens: &[Token]) -> Option<String> {
+    tokens
+        .iter()
+        .map(|t| match t {
+            Token::Char(c) => Some(*c),
+            _ => None,
+        })
+        .collect()
+}
+
+fn match_token(token: &Token, c: char) -> bool {
+    match token {
+        Token::Char(t) => *t == c,
+        Token::Any => true,
+        Token::Star => false,
+        Token::Class { negated, ranges } => {
+            ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated
+        }
+    }
+}
+
+// Wildcard matching, backtracking to the last star on a mismatch.
+fn match_tokens(tokens: &[Token], name: &str) -> bool {
+    let chars = name.chars().collect::<Vec<_>>();
+    let (mut t, mut c) = (0, 0);
+    let mut star = None;
+    while c < chars.len() {
+        if t < tokens.len() && tokens[t] == Token::Star {
+            star = Some((t, c));
+            t += 1;
+        } else if t < tokens.len() && match_token(&tokens[t], chars[c]) {
+            t += 1;
+            c += 1;
+        } else if let Some((st, sc)) = star {
+            t = st + 1;
+            c = sc + 1;
+            star = Some((st, sc + 1));
+        } else {
+            return false;
+        }
+    }
+    tokens[t..].iter().all(|t| *t == Token::Star)
+}
@@ -1,24 +1,38 @@
-use crate::EntryKind;
+use crate::glob::{Pattern, States};
+use crate::{EntryKind, LinkPolicy};
 
 use std::borrow::Cow;
 use std::collections::btree_map::{BTreeMap, Iter};
 use std::collections::vec_deque::VecDeque;
 use std::ffi::{OsStr, OsString};
-use std::path::{Path, PathBuf};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+// Maximum number of symbolic links followed when resolving a path.
+const MAX_LINKS: usize = 40;
 
 /// Directory tree node.
 /// Contains a list of file entries (leaf nodes), and directories (child nodes).
 struct Node<M> {
     files: BTreeMap<OsString, M>,
     dirs: BTreeMap<OsString, Node<M>>,
+    // Directories created explicitly (as opposed to implied by the path of a
+    // file) are kept when they become empty.
+    keep: bool,
 }
 impl<M> Node<M> {
     fn new() -> Self {
         Self {
             files: BTreeMap::new(),
             dirs: BTreeMap::new(),
+            keep: false,
         }
     }
+
+    // Returns true if the node can be removed from its parent.
+    fn prune(&self) -> bool {
+        !self.keep && self.files.is_empty() && self.dirs.is_empty()
+    }
 }
 
 /// Directory index, implemented as a trie.
@@ -78,39 +92,206 @@ pub struct Entry<'a, M> {
     pub kind: EntryKind,
 }
 
+impl<M> Default for Index<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<M> Index<M> {
     pub fn new() -> Self {
         Self { root: Node::new() }
     }
 
-    pub fn entries<P: AsRef<Path>>(&self, path: P) -> Entries<M> {
+    pub fn entries<P: AsRef<Path>>(&self, path: P) -> Entries<'_, M> {
+        let path = normalize_path(path.as_ref());
+        entries(path.iter().collect(), &self.root)
+    }
+
+    /// Removes a file from the index, returning its metadata.
+    ///
+    /// Directories left empty are removed too, unless they were created with
+    /// `insert_dir`.
+    pub fn remove<P: AsRef<Path>>(&mut self, path: P) -> Option<M> {
         let path = normalize_path(path.as_ref());
-        entries(path.into_iter().collect(), &self.root)
+        remove(path.iter().collect(), &mut self.root)
     }
 
-    pub fn remove<P: AsRef<Path>>(&self, _path: P) -> Option<M> {
-        unimplemented!()
+    /// Removes a directory along with all of its contents.
+    /// Returns false if the directory doesn't exist.
+    ///
+    /// Directories left empty are removed too, unless they were created with
+    /// `insert_dir`.
+    pub fn remove_dir_all<P: AsRef<Path>>(&mut self, path: P) -> bool {
+        let path = normalize_path(path.as_ref());
+        if path.iter().next().is_none() {
+            self.clear();
+            return true;
+        }
+        take_dir(path.iter().collect(), &mut self.root).is_some()
+    }
+
+    /// Creates a directory, along with any missing parent directories.
+    /// Files in the way are replaced by directories.
+    ///
+    /// Unlike directories implied by the path of a file, directories created
+    /// this way are kept in the index when they become empty.
+    pub fn insert_dir<P: AsRef<Path>>(&mut self, path: P) {
+        let path = normalize_path(path.as_ref());
+        self.dir_entry(&path).keep = true;
+    }
+
+    /// Moves a file or a directory (along with its contents) to a new path.
+    /// Returns false if there is nothing to move.
+    pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, from: P, to: Q) -> bool {
+        let from = normalize_path(from.as_ref());
+        let to = normalize_path(to.as_ref());
+        if let Some(meta) = remove(from.iter().collect(), &mut self.root) {
+            self.insert(to.into_owned(), meta);
+            true
+        } else if let Some(node) = take_dir(from.iter().collect(), &mut self.root) {
+            match (to.parent(), to.file_name()) {
+                (Some(to_parent), Some(to_name)) => {
+                    let to_parent = self.dir_entry(to_parent);
+                    to_parent.files.remove(to_name);
+                    to_parent.dirs.insert(to_name.to_os_string(), node);
+                }
+                _ => self.root = node,
+            }
+            true
+        } else {
+            false
+        }
     }
 
     pub fn insert<P: Into<PathBuf>>(&mut self, path: P, meta: M) {
         let path = path.into();
         let path = normalize_path(&path);
-        insert(path.into_iter().collect(), &mut self.root, meta)
+        insert(path.iter().collect(), &mut self.root, meta)
     }
 
     pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<&M> {
         let path = normalize_path(path.as_ref());
-        get(path.into_iter().collect(), &self.root)
+        get(path.iter().collect(), &self.root)
     }
 
     pub fn contains<P: AsRef<Path>>(&self, path: P) -> bool {
         self.get(path).is_some()
     }
 
+    /// Returns true if the path is a directory of the index.
+    /// The empty path is the root directory.
+    pub fn contains_dir<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = normalize_path(path.as_ref());
+        let mut node = &self.root;
+        for part in path.iter() {
+            match node.dirs.get(part) {
+                Some(dir) => node = dir,
+                None => return false,
+            }
+        }
+        true
+    }
+
+    /// Resolves the symbolic links in the components of a path.
+    ///
+    /// `link` returns the target of the files that are symbolic links. Relative
+    /// targets are resolved from the directory of the link.
+    pub fn resolve<P, F>(&self, path: P, policy: LinkPolicy, link: F) -> io::Result<PathBuf>
+    where
+        P: AsRef<Path>,
+        F: Fn(&M) -> Option<&Path>,
+    {
+        let path = normalize_path(path.as_ref());
+        if policy == LinkPolicy::NoFollow {
+            return Ok(path.into_owned());
+        }
+        let escapes = || {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "symbolic link points outside of the store",
+            )
+        };
+
+        // components left to resolve, in reverse order
+        let mut pending = path
+            .iter()
+            .rev()
+            .map(OsStr::to_os_string)
+            .collect::<Vec<_>>();
+        let mut real = PathBuf::new();
+        let mut links = 0;
+        while let Some(part) = pending.pop() {
+            if part == ".." {
+                if !real.pop() && policy == LinkPolicy::FollowWithin {
+                    return Err(escapes());
+                }
+                continue;
+            }
+            real.push(&part);
+            let target = match self.get(&real).and_then(&link) {
+                Some(target) => target,
+                None => continue,
+            };
+            links += 1;
+            if links > MAX_LINKS {
+                return Err(io::Error::other("too many levels of symbolic links"));
+            }
+            real.pop();
+            if target.has_root() {
+                if policy == LinkPolicy::FollowWithin {
+                    return Err(escapes());
+                }
+                real = PathBuf::new();
+            }
+            for comp in target.components().rev() {
+                match comp {
+                    Component::Normal(part) => pending.push(part.to_os_string()),
+                    Component::ParentDir => pending.push("..".into()),
+                    _ => {}
+                }
+            }
+        }
+        Ok(real)
+    }
+
+    /// Returns the files & directories that match a glob pattern, along with
+    /// their paths.
+    /// `kind` returns the kind of the entries of files.
+    pub fn glob<F>(&self, pattern: &Pattern, kind: F) -> Vec<(PathBuf, crate::Entry)>
+    where
+        F: Fn(&M) -> EntryKind,
+    {
+        let base = pattern.base();
+        let mut node = &self.root;
+        for part in base.iter() {
+            match node.dirs.get(part) {
+                Some(dir) => node = dir,
+                None => return Vec::new(),
+            }
+        }
+        let mut matches = Vec::new();
+        glob(node, base, &pattern.start(), pattern, &kind, &mut matches);
+        matches
+    }
+
     pub fn clear(&mut self) {
         self.root.files.clear();
         self.root.dirs.clear();
     }
+
+    // Returns the node of a directory, creating it if it doesn't exist.
+    fn dir_entry(&mut self, path: &Path) -> &mut Node<M> {
+        let mut node = &mut self.root;
+        for part in path.iter() {
+            node.files.remove(part);
+            node = node
+                .dirs
+                .entry(part.to_os_string())
+                .or_insert_with(Node::new);
+        }
+        node
+    }
 }
 
 fn entries<'a, M>(mut parts: VecDeque<&OsStr>, node: &'a Node<M>) -> Entries<'a, M> {
@@ -138,7 +319,7 @@ fn insert<M>(mut parts: VecDeque<&OsStr>, node: &mut Node<M>, meta: M) {
     match (f0, parts.front()) {
         (None, _) => {}
         (Some(file), None) => {
-            if node.dirs.get(file).is_none() {
+            if !node.dirs.contains_key(file) {
                 node.files.insert(file.to_os_string(), meta);
             }
         }
@@ -156,6 +337,71 @@ fn insert<M>(mut parts: VecDeque<&OsStr>, node: &mut Node<M>, meta: M) {
     }
 }
 
+fn remove<M>(mut parts: VecDeque<&OsStr>, node: &mut Node<M>) -> Option<M> {
+    let f0 = parts.pop_front();
+    match (f0, parts.front()) {
+        (None, _) => None,
+        (Some(file), None) => node.files.remove(file),
+        (Some(dir), Some(_)) => {
+            let child = node.dirs.get_mut(dir)?;
+            let meta = remove(parts, child);
+            if meta.is_some() && child.prune() {
+                node.dirs.remove(dir);
+            }
+            meta
+        }
+    }
+}
+
+fn take_dir<M>(mut parts: VecDeque<&OsStr>, node: &mut Node<M>) -> Option<Node<M>> {
+    let f0 = parts.pop_front();
+    match (f0, parts.front()) {
+        (None, _) => None,
+        (Some(dir), None) => node.dirs.remove(dir),
+        (Some(dir), Some(_)) => {
+            let child = node.dirs.get_mut(dir)?;
+            let taken = take_dir(parts, child);
+            if taken.is_some() && child.prune() {
+                node.dirs.remove(dir);
+            }
+            taken
+        }
+    }
+}
+
+fn glob<M, F: Fn(&M) -> EntryKind>(
+    node: &Node<M>,
+    path: &Path,
+    states: &States,
+    pattern: &Pattern,
+    kind: &F,
+    matches: &mut Vec<(PathBuf, crate::Entry)>,
+) {
+    let files = node
+        .files
+        .iter()
+        .map(|(name, meta)| (name, kind(meta), None));
+    let dirs = node
+        .dirs
+        .iter()
+        .map(|(name, dir)| (name, EntryKind::Dir, Some(dir)));
+    for (name, entry_kind, dir) in files.chain(dirs) {
+        let (next, matched) = pattern.step(states, name);
+        let path = path.join(name);
+        if matched {
+            let name = name.clone();
+            let entry = crate::Entry {
+                name,
+                kind: entry_kind,
+            };
+            matches.push((path.clone(), entry));
+        }
+        if let (Some(dir), false) = (dir, next.is_empty()) {
+            glob(dir, &path, &next, pattern, kind, matches);
+        }
+    }
+}
+
 fn get<'a, M>(mut parts: VecDeque<&OsStr>, node: &'a Node<M>) -> Option<&'a M> {
     let f0 = parts.pop_front();
     match (f0, parts.front()) {
@@ -182,12 +428,9 @@ fn get<'a, M>(mut parts: VecDeque<&OsStr>, node: &'a Node<M>) -> Option<&'a M> {
 /// assert_eq!(Path::new("foo"), normalize_path(Path::new("./foo")));
 /// ```
 #[doc(hidden)]
-pub fn normalize_path(path: &Path) -> Cow<Path> {
+pub fn normalize_path(path: &Path) -> Cow<'_, Path> {
     use std::path::Component::*;
-    if path.components().any(|c| match c {
-        CurDir | ParentDir => true,
-        _ => false,
-    }) {
+    if path.components().any(|c| matches!(c, CurDir | ParentDir)) {
         let mut normal = PathBuf::new();
         for comp in path.components() {
             match comp {
@@ -4,8 +4,12 @@
 //!
 //! - Access to the local (native) filesystem.
 //! - In-memory filesystems.
-//! - Read from tar, tar.gz, and zip archives.
-//! - Filesystem overlays.
+//! - Read from tar (raw, gzip, bzip2, xz or zstd compressed) and zip archives.
+//! - Write to the local filesystem and in-memory filesystems.
+//! - Filesystem overlays, with whiteouts to hide files of the lower layers.
+//! - Opening files inside of nested archives.
+//! - Packing the contents of any store into tar and zip archives.
+//! - Copying directory trees between stores.
 //!
 //! ## Case sensitivity
 //!
@@ -40,44 +44,66 @@
 //! Don't use this crate in applications where security is a critical factor.
 //! [`LocalFs`] in particular might be vulnerable to [directory traversal
 //! attacks][dir], so it's best not to use it directly in a static file server,
-//! for example.
+//! for example. If you have to, create it with [`LocalFs::sandboxed`], which
+//! rejects paths that resolve outside of its root.
 //!
 //! [`std::fs`]: https://doc.rust-lang.org/std/fs/index.html
 //! [`Store`]: ./trait.Store.html
 //! [`LocalFs`]: ./struct.LocalFs.html
+//! [`LocalFs::sandboxed`]: ./struct.LocalFs.html#method.sandboxed
 //! [dir]: https://en.wikipedia.org/wiki/Directory_traversal_attack
 #![deny(warnings)]
 use std::any::Any;
-use std::collections::LinkedList;
-use std::io::{Cursor, Error, ErrorKind, Read, Result, Seek, SeekFrom};
-use std::path::{Path, PathBuf};
-use std::rc::Rc;
+use std::collections::{BTreeSet, LinkedList};
+use std::ffi::OsString;
+use std::io::{Cursor, Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
 use std::{env, fs};
 
+#[cfg(any(feature = "tar", feature = "zip"))]
+pub use archive::{open_archive, read_archive};
 pub use caseless::CaselessFs;
+pub use glob::{Glob, Pattern};
+#[cfg(any(feature = "tar", feature = "zip"))]
+pub use nested::NestedFs;
 //pub use index::{Index, IndexEntries};
-pub use store::{Entries, Entry, EntryKind, Store, StoreExt};
+pub use store::{
+    Entries, Entry, EntryKind, LinkPolicy, Metadata, Store, StoreExt, StoreMut, StoreMutExt,
+};
 #[cfg(feature = "tar")]
-pub use tar::TarFs;
+pub use tar::{Compression, TarFs};
+pub use walk::Walk;
 #[cfg(feature = "zip")]
 pub use zip::ZipFs;
 
 include!("macros.rs");
 
+#[cfg(any(feature = "tar", feature = "zip"))]
+mod archive;
 pub mod caseless;
+pub mod copy;
+mod glob;
 /// Directory index.
 #[doc(hidden)]
 pub mod index;
+#[cfg(any(feature = "tar", feature = "zip"))]
+pub mod nested;
+#[cfg(any(feature = "tar", feature = "zip"))]
+pub mod pack;
+#[cfg(any(feature = "tar", feature = "zip"))]
+mod section;
 mod store;
 /// Tar file storage.
 #[cfg(feature = "tar")]
 pub mod tar;
+mod walk;
 /// Zip file storage.
 #[cfg(feature = "zip")]
 pub mod zip;
 /// Convenient library imports.
 pub mod prelude {
-    pub use crate::store::{Store, StoreExt};
+    pub use crate::store::{Store, StoreExt, StoreMut, StoreMutExt};
 }
 
 impl_file! {
@@ -103,12 +129,157 @@ impl<T: UserFile> From<T> for File {
     }
 }
 
+impl_file! {
+    /// File you can write to.
+    pub enum FileMut {
+        Local(fs::File),
+        Ram(RamFileMut),
+        // External types are dynamic
+        User(Box<dyn UserFileMut>),
+    }
+}
+
+impl Write for FileMut {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self {
+            FileMut::Local(file) => file.write(buf),
+            FileMut::Ram(file) => file.write(buf),
+            FileMut::User(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            FileMut::Local(file) => file.flush(),
+            FileMut::Ram(file) => file.flush(),
+            FileMut::User(file) => file.flush(),
+        }
+    }
+}
+
+/// Custom writable file type.
+pub trait UserFileMut: Any + Read + Write + Seek + Send {}
+
+impl<T: UserFileMut> From<T> for FileMut {
+    fn from(file: T) -> Self {
+        FileMut::User(Box::new(file))
+    }
+}
+
+// Mounted store, which might also be writable.
+trait MountStore: Store<File = File> + Send + Sync {
+    fn writable(&self) -> Option<&dyn StoreMut<File = File, FileMut = FileMut>>;
+
+    fn into_store(self: Box<Self>) -> Box<dyn Store<File = File> + Send + Sync>;
+}
+
+impl<S, F> MountStore for store::MapFile<S, F>
+where
+    Self: Store<File = File> + Send + Sync + 'static,
+{
+    fn writable(&self) -> Option<&dyn StoreMut<File = File, FileMut = FileMut>> {
+        None
+    }
+
+    fn into_store(self: Box<Self>) -> Box<dyn Store<File = File> + Send + Sync> {
+        self
+    }
+}
+
+// Writable store with its files converted into `File` and `FileMut`.
+struct Writable<S>(S);
+
+impl<S> Store for Writable<S>
+where
+    S: Store,
+    S::File: Into<File>,
+{
+    type File = File;
+
+    fn open_path(&self, path: &Path) -> Result<File> {
+        self.0.open_path(path).map(Into::into)
+    }
+
+    fn entries_path(&self, path: &Path) -> Result<Entries<'_>> {
+        self.0.entries_path(path)
+    }
+
+    fn metadata_path(&self, path: &Path) -> Result<Metadata> {
+        self.0.metadata_path(path)
+    }
+
+    fn read_link_path(&self, path: &Path) -> Result<PathBuf> {
+        self.0.read_link_path(path)
+    }
+
+    fn glob_path(&self, pattern: &Pattern) -> Result<Glob<'_>> {
+        self.0.glob_path(pattern)
+    }
+}
+
+impl<S> StoreMut for Writable<S>
+where
+    S: StoreMut,
+    S::File: Into<File>,
+    S::FileMut: Into<FileMut>,
+{
+    type FileMut = FileMut;
+
+    fn create_path(&self, path: &Path) -> Result<FileMut> {
+        self.0.create_path(path).map(Into::into)
+    }
+
+    fn write_path(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.0.write_path(path, data)
+    }
+
+    fn remove_file_path(&self, path: &Path) -> Result<()> {
+        self.0.remove_file_path(path)
+    }
+
+    fn remove_dir_path(&self, path: &Path) -> Result<()> {
+        self.0.remove_dir_path(path)
+    }
+
+    fn remove_dir_all_path(&self, path: &Path) -> Result<()> {
+        self.0.remove_dir_all_path(path)
+    }
+
+    fn create_dir_all_path(&self, path: &Path) -> Result<()> {
+        self.0.create_dir_all_path(path)
+    }
+
+    fn rename_path(&self, from: &Path, to: &Path) -> Result<()> {
+        self.0.rename_path(from, to)
+    }
+}
+
+impl<S> MountStore for Writable<S>
+where
+    S: StoreMut + Send + Sync + 'static,
+    S::File: Into<File>,
+    S::FileMut: Into<FileMut>,
+{
+    fn writable(&self) -> Option<&dyn StoreMut<File = File, FileMut = FileMut>> {
+        Some(self)
+    }
+
+    fn into_store(self: Box<Self>) -> Box<dyn Store<File = File> + Send + Sync> {
+        self
+    }
+}
+
 struct Mount {
     path: PathBuf,
-    store: Box<dyn Store<File = File>>,
+    store: Box<dyn MountStore>,
+    // Falls through to the mounts below when a path is not found.
+    union: bool,
 }
 
 /// Virtual filesystem.
+///
+/// Mounted stores must be `Send + Sync`, so a single `MiniFs` can be shared
+/// between threads (behind an `Arc`, for example).
 pub struct MiniFs {
     mount: LinkedList<Mount>,
 }
@@ -117,35 +288,130 @@ impl Store for MiniFs {
     type File = File;
 
     fn open_path(&self, path: &Path) -> Result<File> {
-        let next = self.mount.iter().rev().find_map(|mnt| {
-            if let Ok(np) = path.strip_prefix(&mnt.path) {
-                Some((np, &mnt.store))
-            } else {
-                None
+        for (np, mnt) in self.mounted(path) {
+            match mnt.store.open_path(np) {
+                Err(ref e) if e.kind() == ErrorKind::NotFound && mnt.union => {}
+                file => return file,
+            }
+        }
+        Err(Error::from(ErrorKind::NotFound))
+    }
+
+    fn metadata_path(&self, path: &Path) -> Result<Metadata> {
+        let mut meta = Err(Error::from(ErrorKind::NotFound));
+        for (np, mnt) in self.mounted(path) {
+            meta = mnt.store.metadata_path(np);
+            match meta {
+                Err(ref e) if e.kind() == ErrorKind::NotFound && mnt.union => {}
+                _ => break,
+            }
+        }
+        match meta {
+            Err(ref e) if e.kind() == ErrorKind::NotFound && self.has_mount_points(path) => {
+                Ok(Metadata::dir())
+            }
+            meta => meta,
+        }
+    }
+
+    fn read_link_path(&self, path: &Path) -> Result<PathBuf> {
+        for (np, mnt) in self.mounted(path) {
+            match mnt.store.read_link_path(np) {
+                Err(ref e) if e.kind() == ErrorKind::NotFound && mnt.union => {}
+                target => return target,
+            }
+        }
+        Err(Error::from(ErrorKind::NotFound))
+    }
+
+    /// Returns the entries of the store mounted on the path, along with the
+    /// mount points under it (listed as directories).
+    ///
+    /// Listings of union mounts are merged with the ones of the stores below.
+    fn entries_path(&self, path: &Path) -> Result<Entries<'_>> {
+        let dirs = self.mount_points(path);
+        let mounted = self.mounted(path);
+        let mut listings = Vec::new();
+        for &(np, mnt) in &mounted {
+            match mnt.store.entries_path(np) {
+                Ok(entries) => listings.push(entries),
+                Err(ref e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+            if !mnt.union {
+                break;
             }
-        });
-        if let Some((np, store)) = next {
-            store.open_path(np)
-        } else {
-            Err(Error::from(ErrorKind::NotFound))
         }
+        if listings.is_empty() && dirs.is_empty() && !mounted.is_empty() {
+            return Err(Error::from(ErrorKind::NotFound));
+        }
+
+        let mount_points = dirs
+            .iter()
+            .map(|name| {
+                Ok(Entry {
+                    name: name.clone(),
+                    kind: EntryKind::Dir,
+                })
+            })
+            .collect::<Vec<_>>();
+        // mount points shadow the entries of the stores with the same name, and
+        // stores shadow the entries of the ones below them.
+        let mut seen = dirs;
+        let entries = listings.into_iter().flatten().filter(move |e| match e {
+            Ok(e) => seen.insert(e.name.clone()),
+            Err(_) => true,
+        });
+        Ok(Entries::new(mount_points.into_iter().chain(entries)))
+    }
+}
+
+impl StoreMut for MiniFs {
+    type FileMut = FileMut;
+
+    fn create_path(&self, path: &Path) -> Result<FileMut> {
+        let (_, np, store) = self.writable(path)?;
+        store.create_path(np)
+    }
+
+    fn write_path(&self, path: &Path, data: &[u8]) -> Result<()> {
+        let (_, np, store) = self.writable(path)?;
+        store.write_path(np, data)
+    }
+
+    fn remove_file_path(&self, path: &Path) -> Result<()> {
+        let (_, np, store) = self.writable(path)?;
+        store.remove_file_path(np)
     }
 
-    fn entries_path(&self, path: &Path) -> Result<Entries> {
-        // FIXME creating a new PathBuf because otherwise I'm getting lifetime errors.
-        let path = path.to_path_buf();
+    fn remove_dir_path(&self, path: &Path) -> Result<()> {
+        let (_, np, store) = self.writable(path)?;
+        store.remove_dir_path(np)
+    }
+
+    fn remove_dir_all_path(&self, path: &Path) -> Result<()> {
+        let (_, np, store) = self.writable(path)?;
+        store.remove_dir_all_path(np)
+    }
+
+    fn create_dir_all_path(&self, path: &Path) -> Result<()> {
+        let (_, np, store) = self.writable(path)?;
+        store.create_dir_all_path(np)
+    }
+
+    fn rename_path(&self, from: &Path, to: &Path) -> Result<()> {
+        let (from_mount, from, store) = self.writable(from)?;
+        let (to_mount, to, _) = self.writable(to)?;
+        if from_mount != to_mount {
+            return Err(Error::other("can't rename across different mounts"));
+        }
+        store.rename_path(from, to)
+    }
+}
 
-        Ok(Entries::new(
-            self.mount
-                .iter()
-                .rev()
-                .find(|m| path.strip_prefix(&m.path).is_ok())
-                .into_iter()
-                .flat_map(move |m| match path.strip_prefix(&m.path) {
-                    Ok(np) => m.store.entries_path(np).unwrap(),
-                    Err(_) => Entries::new(None),
-                }),
-        ))
+impl Default for MiniFs {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -159,65 +425,176 @@ impl MiniFs {
     pub fn mount<P, S, T>(mut self, path: P, store: S) -> Self
     where
         P: Into<PathBuf>,
-        S: Store<File = T> + 'static,
+        S: Store<File = T> + Send + Sync + 'static,
         T: Into<File>,
     {
         let path = path.into();
         let store = Box::new(store::MapFile::new(store, |file: T| file.into()));
-        self.mount.push_back(Mount { path, store });
+        self.mount.push_back(Mount {
+            path,
+            store,
+            union: false,
+        });
+        self
+    }
+
+    /// Mounts a store that can also be written to.
+    ///
+    /// Writes to a path are routed to the last writable store mounted on a
+    /// prefix of the path.
+    pub fn mount_mut<P, S>(mut self, path: P, store: S) -> Self
+    where
+        P: Into<PathBuf>,
+        S: StoreMut + Send + Sync + 'static,
+        S::File: Into<File>,
+        S::FileMut: Into<FileMut>,
+    {
+        let path = path.into();
+        let store = Box::new(Writable(store));
+        self.mount.push_back(Mount {
+            path,
+            store,
+            union: false,
+        });
+        self
+    }
+
+    /// Turns the last mounted store into a union mount.
+    ///
+    /// Paths that are not found in a union mount are looked up in the stores
+    /// mounted before it, and its listings are merged with theirs.
+    ///
+    /// ```no_run
+    /// use mini_fs::{LocalFs, MiniFs};
+    ///
+    /// let fs = MiniFs::new()
+    ///     .mount("/data", LocalFs::new("data/"))
+    ///     .mount("/data", LocalFs::new("patch/"))
+    ///     .union();
+    /// ```
+    pub fn union(mut self) -> Self {
+        if let Some(mnt) = self.mount.back_mut() {
+            mnt.union = true;
+        }
         self
     }
 
-    pub fn umount<P>(&mut self, path: P) -> Option<Box<dyn Store<File = File>>>
+    pub fn umount<P>(&mut self, path: P) -> Option<Box<dyn Store<File = File> + Send + Sync>>
     where
         P: AsRef<Path>,
     {
         let path = path.as_ref();
         if let Some(p) = self.mount.iter().rposition(|p| p.path == path) {
             let mut tail = self.mount.split_off(p);
-            let fs = tail.pop_front().map(|m| m.store);
+            let fs = tail.pop_front().map(|m| m.store.into_store());
             self.mount.append(&mut tail);
             fs
         } else {
             None
         }
     }
+
+    // Returns the mounts the path is under, from the most recent one, along
+    // with the path relative to each of them.
+    fn mounted<'a, 'p>(&'a self, path: &'p Path) -> Vec<(&'p Path, &'a Mount)> {
+        self.mount
+            .iter()
+            .rev()
+            .filter_map(|mnt| path.strip_prefix(&mnt.path).ok().map(|np| (np, mnt)))
+            .collect()
+    }
+
+    // Returns the names of the directories under the path that lead to a mount
+    // point.
+    fn mount_points(&self, path: &Path) -> BTreeSet<OsString> {
+        self.mount
+            .iter()
+            .filter_map(|mnt| match mnt.path.strip_prefix(path) {
+                Ok(np) => match np.components().next() {
+                    Some(Component::Normal(name)) => Some(name.to_os_string()),
+                    _ => None,
+                },
+                Err(_) => None,
+            })
+            .collect()
+    }
+
+    // Returns true if there is a mount point under the path.
+    fn has_mount_points(&self, path: &Path) -> bool {
+        self.mount
+            .iter()
+            .any(|mnt| mnt.path != path && mnt.path.starts_with(path))
+    }
+
+    // Finds the writable store the path should be written to. Returns the
+    // position of the mount, along with the path relative to it.
+    #[allow(clippy::type_complexity)]
+    fn writable<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> Result<(
+        usize,
+        &'a Path,
+        &'a dyn StoreMut<File = File, FileMut = FileMut>,
+    )> {
+        let mut found = false;
+        for (i, mnt) in self.mount.iter().enumerate().rev() {
+            if let Ok(np) = path.strip_prefix(&mnt.path) {
+                found = true;
+                if let Some(store) = mnt.store.writable() {
+                    return Ok((i, np, store));
+                }
+            }
+        }
+        if found {
+            Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "path is mounted on a read-only store",
+            ))
+        } else {
+            Err(Error::from(ErrorKind::NotFound))
+        }
+    }
 }
 
 /// Native file store.
 pub struct LocalFs {
     root: PathBuf,
+    sandboxed: bool,
+    links: LinkPolicy,
 }
 
 impl Store for LocalFs {
     type File = fs::File;
 
     fn open_path(&self, path: &Path) -> Result<fs::File> {
+        if self.links == LinkPolicy::NoFollow
+            && fs::symlink_metadata(self.resolve_link(path)?)?
+                .file_type()
+                .is_symlink()
+        {
+            return Err(store::link_error());
+        }
         fs::OpenOptions::new()
             .create(false)
             .read(true)
             .write(false)
-            .open(self.root.join(path))
+            .open(self.resolve(path)?)
     }
 
-    fn entries_path(&self, path: &Path) -> Result<Entries> {
-        // FIXME cloned because lifetimes.
-        //let root = self.root.clone();
-
-        let entries = fs::read_dir(self.root.join(path))?.map(move |ent| {
+    fn entries_path(&self, path: &Path) -> Result<Entries<'_>> {
+        // names are relative to the root, which the resolved path may not
+        // start with once symlinks are followed
+        let dir = index::normalize_path(path).into_owned();
+        let entries = fs::read_dir(self.resolve(path)?)?.map(move |ent| {
             let entry = ent?;
-            let path = entry
-                .path()
-                .strip_prefix(&self.root)
-                .map(Path::to_path_buf)
-                .expect("Error striping path suffix.");
+            let path = dir.join(entry.file_name());
             let file_type = entry.file_type()?;
 
-            // TODO synlinks
             let kind = if file_type.is_dir() {
                 EntryKind::Dir
             } else if file_type.is_symlink() {
-                EntryKind::File
+                EntryKind::Symlink
             } else {
                 EntryKind::File
             };
@@ -230,11 +607,183 @@ impl Store for LocalFs {
 
         Ok(Entries::new(entries))
     }
+
+    fn metadata_path(&self, path: &Path) -> Result<Metadata> {
+        let meta = if self.links == LinkPolicy::NoFollow {
+            fs::symlink_metadata(self.resolve_link(path)?)?
+        } else {
+            fs::metadata(self.resolve(path)?)?
+        };
+        let kind = if meta.is_dir() {
+            EntryKind::Dir
+        } else if meta.file_type().is_symlink() {
+            EntryKind::Symlink
+        } else {
+            EntryKind::File
+        };
+        #[cfg(unix)]
+        let permissions = {
+            use std::os::unix::fs::PermissionsExt;
+            Some(meta.permissions().mode() & 0o7777)
+        };
+        #[cfg(not(unix))]
+        let permissions = None;
+
+        Ok(Metadata {
+            kind,
+            len: if meta.is_dir() { 0 } else { meta.len() },
+            modified: meta.modified().ok(),
+            permissions,
+            crc32: None,
+        })
+    }
+
+    fn read_link_path(&self, path: &Path) -> Result<PathBuf> {
+        fs::read_link(self.resolve_link(path)?)
+    }
+}
+
+impl StoreMut for LocalFs {
+    type FileMut = fs::File;
+
+    fn create_path(&self, path: &Path) -> Result<fs::File> {
+        fs::OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(true)
+            .open(self.resolve(path)?)
+    }
+
+    fn write_path(&self, path: &Path, data: &[u8]) -> Result<()> {
+        fs::write(self.resolve(path)?, data)
+    }
+
+    fn remove_file_path(&self, path: &Path) -> Result<()> {
+        fs::remove_file(self.resolve_link(path)?)
+    }
+
+    fn remove_dir_path(&self, path: &Path) -> Result<()> {
+        fs::remove_dir(self.resolve_link(path)?)
+    }
+
+    fn remove_dir_all_path(&self, path: &Path) -> Result<()> {
+        fs::remove_dir_all(self.resolve_link(path)?)
+    }
+
+    fn create_dir_all_path(&self, path: &Path) -> Result<()> {
+        fs::create_dir_all(self.resolve(path)?)
+    }
+
+    fn rename_path(&self, from: &Path, to: &Path) -> Result<()> {
+        fs::rename(self.resolve_link(from)?, self.resolve_link(to)?)
+    }
 }
 
 impl LocalFs {
     pub fn new<P: Into<PathBuf>>(root: P) -> Self {
-        Self { root: root.into() }
+        Self {
+            root: root.into(),
+            sandboxed: false,
+            links: LinkPolicy::default(),
+        }
+    }
+
+    /// Creates a store that can't access anything outside of `root`.
+    ///
+    /// Absolute paths and paths that escape the root (including through
+    /// symlinks) are rejected with a `PermissionDenied` error. This includes
+    /// paths with a leading `/`, so use `open("index.html")` rather than
+    /// `open("/index.html")`.
+    ///
+    /// # Remarks
+    ///
+    /// Paths are checked before they are accessed, so a symlink swapped in
+    /// between the check and the access by another process isn't detected.
+    pub fn sandboxed<P: Into<PathBuf>>(root: P) -> Result<Self> {
+        Ok(Self {
+            root: fs::canonicalize(root.into())?,
+            sandboxed: true,
+            links: LinkPolicy::default(),
+        })
+    }
+
+    /// Sets how symbolic links are resolved. Defaults to `LinkPolicy::Follow`.
+    ///
+    /// With `LinkPolicy::FollowWithin`, paths are checked the same way as in a
+    /// [sandboxed](#method.sandboxed) store. With `LinkPolicy::NoFollow`, only
+    /// the last component of a path is checked for symbolic links.
+    pub fn link_policy(mut self, policy: LinkPolicy) -> Self {
+        self.links = policy;
+        self
+    }
+
+    // Resolves a path relative to the root of the store.
+    fn resolve(&self, path: &Path) -> Result<PathBuf> {
+        if !self.sandboxed && self.links != LinkPolicy::FollowWithin {
+            return Ok(self.root.join(path));
+        }
+        let denied = || {
+            Error::new(
+                ErrorKind::PermissionDenied,
+                "path escapes the root of the store",
+            )
+        };
+        let root = if self.sandboxed {
+            self.root.clone()
+        } else {
+            fs::canonicalize(&self.root)?
+        };
+
+        let mut joined = root.clone();
+        for comp in path.components() {
+            match comp {
+                Component::Normal(part) => joined.push(part),
+                Component::CurDir => {}
+                _ => return Err(denied()),
+            }
+        }
+
+        // Resolve symlinks. Paths that don't exist yet are resolved from their
+        // closest existing ancestor.
+        let mut existing = joined.as_path();
+        let mut missing = Vec::new();
+        let real = loop {
+            match fs::canonicalize(existing) {
+                Ok(mut real) => {
+                    real.extend(missing.iter().rev());
+                    break real;
+                }
+                Err(ref e) if e.kind() == ErrorKind::NotFound => {
+                    // dangling symlink
+                    if fs::symlink_metadata(existing).is_ok() {
+                        return Err(denied());
+                    }
+                    match (existing.parent(), existing.file_name()) {
+                        (Some(parent), Some(name)) => {
+                            missing.push(name);
+                            existing = parent;
+                        }
+                        _ => return Err(Error::from(ErrorKind::NotFound)),
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        if real.starts_with(&root) {
+            Ok(real)
+        } else {
+            Err(denied())
+        }
+    }
+
+    // Resolves a path without following a symbolic link in its last component.
+    fn resolve_link(&self, path: &Path) -> Result<PathBuf> {
+        match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => Ok(self.resolve(parent)?.join(name)),
+            _ => self.resolve(path),
+        }
     }
 
     /// Point to the current working directory.
@@ -245,11 +794,11 @@ impl LocalFs {
 
 /// In-memory file storage
 pub struct RamFs {
-    index: index::Index<Rc<[u8]>>,
+    index: Arc<RwLock<index::Index<Arc<[u8]>>>>,
 }
 
 /// In-memory file.
-pub struct RamFile(Cursor<Rc<[u8]>>);
+pub struct RamFile(Cursor<Arc<[u8]>>);
 
 impl Read for RamFile {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
@@ -263,47 +812,236 @@ impl Seek for RamFile {
     }
 }
 
+/// In-memory file open for writing.
+///
+/// Written data is stored in the filesystem when the file is flushed or
+/// dropped. Data written after the file has been removed or renamed is
+/// discarded.
+pub struct RamFileMut {
+    inner: Cursor<Vec<u8>>,
+    path: PathBuf,
+    index: Arc<RwLock<index::Index<Arc<[u8]>>>>,
+    dirty: bool,
+}
+
+impl RamFileMut {
+    fn commit(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+        let mut index = self.index.write().unwrap();
+        if index.contains(&self.path) {
+            let data = self.inner.get_ref().as_slice().into();
+            index.insert(self.path.clone(), data);
+        }
+    }
+}
+
+impl Read for RamFileMut {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Write for RamFileMut {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.dirty = true;
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.commit();
+        Ok(())
+    }
+}
+
+impl Seek for RamFileMut {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl Drop for RamFileMut {
+    fn drop(&mut self) {
+        self.commit();
+    }
+}
+
 impl Store for RamFs {
     type File = RamFile;
 
     fn open_path(&self, path: &Path) -> Result<Self::File> {
-        match self.index.get(path) {
-            Some(file) => Ok(RamFile(Cursor::new(Rc::clone(file)))),
+        match self.index.read().unwrap().get(path) {
+            Some(file) => Ok(RamFile(Cursor::new(Arc::clone(file)))),
             None => Err(Error::from(ErrorKind::NotFound)),
         }
     }
 
-    fn entries_path(&self, path: &Path) -> Result<Entries> {
-        Ok(Entries::new(self.index.entries(path).map(|ent| {
-            Ok(Entry {
-                name: ent.name.to_os_string(),
-                kind: ent.kind,
+    fn entries_path(&self, path: &Path) -> Result<Entries<'_>> {
+        let index = self.index.read().unwrap();
+        let entries: Vec<_> = index
+            .entries(path)
+            .map(|ent| {
+                Ok(Entry {
+                    name: ent.name.to_os_string(),
+                    kind: ent.kind,
+                })
             })
-        })))
+            .collect();
+        Ok(Entries::new(entries))
+    }
+
+    fn metadata_path(&self, path: &Path) -> Result<Metadata> {
+        let index = self.index.read().unwrap();
+        match index.get(path) {
+            Some(file) => Ok(Metadata {
+                kind: EntryKind::File,
+                len: file.len() as u64,
+                modified: None,
+                permissions: None,
+                crc32: None,
+            }),
+            None if index.contains_dir(path) => Ok(Metadata::dir()),
+            None => Err(Error::from(ErrorKind::NotFound)),
+        }
+    }
+
+    fn glob_path(&self, pattern: &Pattern) -> Result<Glob<'_>> {
+        let index = self.index.read().unwrap();
+        Ok(Glob::index(&index, pattern, |_| EntryKind::File))
+    }
+}
+
+impl StoreMut for RamFs {
+    type FileMut = RamFileMut;
+
+    fn create_path(&self, path: &Path) -> Result<RamFileMut> {
+        self.insert_file(path, Arc::new([]))?;
+        Ok(RamFileMut {
+            inner: Cursor::new(Vec::new()),
+            path: path.to_path_buf(),
+            index: Arc::clone(&self.index),
+            dirty: false,
+        })
+    }
+
+    fn write_path(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.insert_file(path, data.into())
+    }
+
+    fn remove_file_path(&self, path: &Path) -> Result<()> {
+        match self.index.write().unwrap().remove(path) {
+            Some(_) => Ok(()),
+            None => Err(Error::from(ErrorKind::NotFound)),
+        }
+    }
+
+    fn remove_dir_path(&self, path: &Path) -> Result<()> {
+        let mut index = self.index.write().unwrap();
+        if !index.contains_dir(path) {
+            return Err(Error::from(ErrorKind::NotFound));
+        }
+        if index.entries(path).next().is_some() {
+            return Err(Error::other("directory is not empty"));
+        }
+        index.remove_dir_all(path);
+        Ok(())
+    }
+
+    fn remove_dir_all_path(&self, path: &Path) -> Result<()> {
+        if self.index.write().unwrap().remove_dir_all(path) {
+            Ok(())
+        } else {
+            Err(Error::from(ErrorKind::NotFound))
+        }
+    }
+
+    fn create_dir_all_path(&self, path: &Path) -> Result<()> {
+        let mut index = self.index.write().unwrap();
+        if path.ancestors().any(|p| index.contains(p)) {
+            return Err(Error::new(ErrorKind::AlreadyExists, "path is a file"));
+        }
+        index.insert_dir(path);
+        Ok(())
+    }
+
+    fn rename_path(&self, from: &Path, to: &Path) -> Result<()> {
+        if to.starts_with(from) && to != from {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "can't move a directory into itself",
+            ));
+        }
+        let mut index = self.index.write().unwrap();
+        if to.ancestors().skip(1).any(|p| index.contains(p)) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                "parent path is a file",
+            ));
+        }
+        if to != from && index.contains_dir(to) {
+            if index.contains(from) {
+                return Err(Error::new(ErrorKind::InvalidInput, "path is a directory"));
+            }
+            if index.entries(to).next().is_some() {
+                return Err(Error::other("directory is not empty"));
+            }
+        }
+        if to != from && index.contains(to) && index.contains_dir(from) {
+            return Err(Error::new(ErrorKind::InvalidInput, "path is a file"));
+        }
+        if index.rename(from, to) {
+            Ok(())
+        } else {
+            Err(Error::from(ErrorKind::NotFound))
+        }
+    }
+}
+
+impl Default for RamFs {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl RamFs {
     pub fn new() -> Self {
         Self {
-            index: index::Index::new(),
+            index: Arc::new(RwLock::new(index::Index::new())),
         }
     }
 
     pub fn clear(&mut self) {
-        self.index.clear();
+        self.index.write().unwrap().clear();
+    }
+
+    // Inserts a file, unless the path or one of its parents is in the way.
+    fn insert_file(&self, path: &Path, data: Arc<[u8]>) -> Result<()> {
+        let mut index = self.index.write().unwrap();
+        if index.contains_dir(path) {
+            return Err(Error::new(ErrorKind::InvalidInput, "path is a directory"));
+        }
+        if path.ancestors().skip(1).any(|p| index.contains(p)) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                "parent path is a file",
+            ));
+        }
+        index.insert(path, data);
+        Ok(())
     }
 
-    pub fn rm<P: AsRef<Path>>(&mut self, path: P) -> Option<Rc<[u8]>> {
-        self.index.remove(path)
+    pub fn rm<P: AsRef<Path>>(&mut self, path: P) -> Option<Arc<[u8]>> {
+        self.index.write().unwrap().remove(path)
     }
 
     pub fn touch<P, F>(&mut self, path: P, file: F)
     where
         P: Into<PathBuf>,
-        F: Into<Rc<[u8]>>,
+        F: Into<Arc<[u8]>>,
     {
-        self.index.insert(path.into(), file.into());
+        self.index.write().unwrap().insert(path.into(), file.into());
     }
 
     pub fn index(self) -> Self {
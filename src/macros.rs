@@ -58,13 +58,6 @@ macro_rules! impl_file {
     }
 }
 
-macro_rules! store_entries {
-    ($self:ident, $path:expr, $head:ident,) => { $self.0.entries_path($path)? };
-    ($self:ident, $path:expr, $head:ident, $($tail:ident,)+) => {
-        $self.0.entries_path($path)?.chain(store_entries!($self, $path, $($tail,)+) )
-    }
-}
-
 macro_rules! store_tuples {
     ($head:ident,) => {};
     ($head:ident, $($tail:ident,)+) => {
@@ -79,26 +72,104 @@ macro_rules! store_tuples {
             #[allow(non_snake_case)]
             fn open_path(&self, path: &Path) -> io::Result<Self::File> {
                 let ($head, $($tail,)+) = self;
+                if is_marker(path) {
+                    return Err(io::Error::from(io::ErrorKind::NotFound));
+                }
                 match $head.open_path(path) {
                     Ok(file) => return Ok(file.into()),
                     Err(ref err) if err.kind() == io::ErrorKind::NotFound => {},
                     Err(err) => return Err(err),
                 }
+                if hides($head, path) {
+                    return Err(io::Error::from(io::ErrorKind::NotFound));
+                }
                 $(
                 match $tail.open_path(path) {
                     Ok(file) => return Ok(file.into()),
                     Err(ref err) if err.kind() == io::ErrorKind::NotFound => {},
                     Err(err) => return Err(err),
                 }
+                if hides($tail, path) {
+                    return Err(io::Error::from(io::ErrorKind::NotFound));
+                }
+                )+
+
+                Err(io::Error::from(io::ErrorKind::NotFound))
+            }
+
+            #[allow(non_snake_case)]
+            fn entries_path(&self, path: &Path) -> io::Result<Entries<'_>> {
+                let ($head, $($tail,)+) = self;
+                // list the elements of the tuple down to the first one that
+                // covers the rest
+                let mut layers = Vec::new();
+                'layers: {
+                    if push_layer(&mut layers, $head, path)? {
+                        break 'layers;
+                    }
+                    $(
+                    if push_layer(&mut layers, $tail, path)? {
+                        break 'layers;
+                    }
+                    )+
+                }
+                Ok(Entries::new(OverlayEntries::new(layers)))
+            }
+
+            #[allow(non_snake_case)]
+            fn metadata_path(&self, path: &Path) -> io::Result<Metadata> {
+                let ($head, $($tail,)+) = self;
+                if is_marker(path) {
+                    return Err(io::Error::from(io::ErrorKind::NotFound));
+                }
+                match $head.metadata_path(path) {
+                    Ok(meta) => return Ok(meta),
+                    Err(ref err) if err.kind() == io::ErrorKind::NotFound => {},
+                    Err(err) => return Err(err),
+                }
+                if hides($head, path) {
+                    return Err(io::Error::from(io::ErrorKind::NotFound));
+                }
+                $(
+                match $tail.metadata_path(path) {
+                    Ok(meta) => return Ok(meta),
+                    Err(ref err) if err.kind() == io::ErrorKind::NotFound => {},
+                    Err(err) => return Err(err),
+                }
+                if hides($tail, path) {
+                    return Err(io::Error::from(io::ErrorKind::NotFound));
+                }
                 )+
 
                 Err(io::Error::from(io::ErrorKind::NotFound))
             }
 
-            fn entries_path(&self, path: &Path) -> io::Result<Entries> {
-                // chain all elements from the tuple
-                let raw = store_entries!(self, path, $head, $($tail,)+);
-                Ok(Entries::new(TupleEntries::new(raw)))
+            #[allow(non_snake_case)]
+            fn read_link_path(&self, path: &Path) -> io::Result<PathBuf> {
+                let ($head, $($tail,)+) = self;
+                if is_marker(path) {
+                    return Err(io::Error::from(io::ErrorKind::NotFound));
+                }
+                match $head.read_link_path(path) {
+                    Ok(target) => return Ok(target),
+                    Err(ref err) if err.kind() == io::ErrorKind::NotFound => {},
+                    Err(err) => return Err(err),
+                }
+                if hides($head, path) {
+                    return Err(io::Error::from(io::ErrorKind::NotFound));
+                }
+                $(
+                match $tail.read_link_path(path) {
+                    Ok(target) => return Ok(target),
+                    Err(ref err) if err.kind() == io::ErrorKind::NotFound => {},
+                    Err(err) => return Err(err),
+                }
+                if hides($tail, path) {
+                    return Err(io::Error::from(io::ErrorKind::NotFound));
+                }
+                )+
+
+                Err(io::Error::from(io::ErrorKind::NotFound))
             }
         }
         store_tuples!($($tail,)+);
@@ -0,0 +1,183 @@
+//! This module contains a filesystem that looks into nested archives.
+//!
+//! A nested filesystem wraps an inner filesystem and treats the archives in it
+//! as directories. A path that passes through an archive
+//! (`packs/dlc1.zip/textures/a.png`) opens the archive from the inner
+//! filesystem and resolves the rest of the path inside of it. Archives inside
+//! of archives are looked into as well.
+//!
+//! Archives are recognized by the extension of their name (`.zip`, `.tar`,
+//! `.tar.gz`, `.tgz` and the like), and their format is detected from their
+//! contents with [`read_archive`]. Files with an archive extension that turn
+//! out not to be archives are left as regular files.
+//!
+//! [`read_archive`]: ../fn.read_archive.html
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::store::{Entries, Entry, EntryKind, Metadata, Store};
+use crate::{read_archive, File};
+
+/// Extensions of the files that are treated as archives.
+const EXTENSIONS: &[&str] = &[
+    ".zip", ".tar", ".tar.gz", ".tgz", ".tar.bz2", ".tbz2", ".tar.xz", ".txz", ".tar.zst", ".tzst",
+];
+
+type Archive = NestedFs<Box<dyn Store<File = File> + Send + Sync>>;
+
+/// Nested filesystem wrapping an inner filesystem.
+pub struct NestedFs<S> {
+    /// Inner filesystem store.
+    inner: S,
+    /// Archives opened so far, by their path in the inner filesystem. Files
+    /// that turned out not to be archives are kept as None.
+    archives: Mutex<HashMap<PathBuf, Option<Arc<Archive>>>>,
+}
+
+impl<S> NestedFs<S>
+where
+    S: Store,
+    S::File: Into<File>,
+{
+    /// Creates a new nested filesystem with the provided inner filesystem.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            archives: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Moves the inner filesystem out of the nested filesystem.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Gets a reference to the inner filesystem.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Splits a path at the first archive it passes through. Returns the
+    /// archive, along with the rest of the path.
+    fn split<'p>(&self, path: &'p Path) -> io::Result<Option<(Arc<Archive>, &'p Path)>> {
+        let mut prefix = PathBuf::new();
+        let mut components = path.components();
+        while let Some(component) = components.next() {
+            prefix.push(component);
+            if let Component::Normal(name) = component {
+                if is_archive(name) {
+                    if let Some(archive) = self.archive(&prefix)? {
+                        return Ok(Some((archive, components.as_path())));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Opens the archive in the given path, or returns the cached one.
+    /// Returns None if there isn't an archive in the path.
+    fn archive(&self, path: &Path) -> io::Result<Option<Arc<Archive>>> {
+        if let Some(archive) = self.archives.lock().unwrap().get(path) {
+            return Ok(archive.clone());
+        }
+        match self.inner.metadata_path(path) {
+            Ok(ref meta) if !meta.is_file() => return Ok(None),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            // stores without metadata may still be able to open the file
+            _ => {}
+        }
+        let file = match self.inner.open_path(path) {
+            Ok(file) => file.into(),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        // the archive is read without holding the lock, so other archives can
+        // be used in the meantime.
+        let archive = match read_archive(file) {
+            Ok(store) => Some(Arc::new(NestedFs::new(store))),
+            Err(ref e) if e.kind() == io::ErrorKind::InvalidData => None,
+            Err(e) => return Err(e),
+        };
+        let mut archives = self.archives.lock().unwrap();
+        Ok(archives
+            .entry(path.to_path_buf())
+            .or_insert(archive)
+            .clone())
+    }
+}
+
+impl<S> Store for NestedFs<S>
+where
+    S: Store,
+    S::File: Into<File>,
+{
+    type File = File;
+
+    /// Opens the file identified by path. Paths that name an archive open the
+    /// archive file itself.
+    fn open_path(&self, path: &Path) -> io::Result<Self::File> {
+        match self.split(path)? {
+            Some((archive, rest)) if rest != Path::new("") => archive.open_path(rest),
+            _ => self.inner.open_path(path).map(Into::into),
+        }
+    }
+
+    /// Iterates over the entries of a directory or an archive. Archives are
+    /// listed as directories, so files with an archive extension are opened to
+    /// tell whether they are archives.
+    fn entries_path(&self, path: &Path) -> io::Result<Entries<'_>> {
+        if let Some((archive, rest)) = self.split(path)? {
+            // the listing would borrow the archive, which is shared with the cache
+            let entries = archive.entries_path(rest)?.collect::<Vec<_>>();
+            return Ok(Entries::new(entries));
+        }
+        let dir = path.to_path_buf();
+        let entries = self.inner.entries_path(path)?.map(move |entry| {
+            let entry = entry?;
+            let name = crate::walk::file_name(&entry);
+            let kind = match entry.kind {
+                EntryKind::File if is_archive(name) && self.archive(&dir.join(name))?.is_some() => {
+                    EntryKind::Dir
+                }
+                kind => kind,
+            };
+            Ok(Entry { kind, ..entry })
+        });
+        Ok(Entries::new(entries))
+    }
+
+    /// Returns the metadata of the file identified by path. Archives are
+    /// reported as directories.
+    fn metadata_path(&self, path: &Path) -> io::Result<Metadata> {
+        match self.split(path)? {
+            Some((_, rest)) if rest == Path::new("") => Ok(Metadata::dir()),
+            Some((archive, rest)) => archive.metadata_path(rest),
+            None => self.inner.metadata_path(path),
+        }
+    }
+
+    fn read_link_path(&self, path: &Path) -> io::Result<PathBuf> {
+        match self.split(path)? {
+            Some((archive, rest)) if rest != Path::new("") => archive.read_link_path(rest),
+            _ => self.inner.read_link_path(path),
+        }
+    }
+}
+
+/// Returns true if the name has the extension of an archive.
+fn is_archive(name: &OsStr) -> bool {
+    match name.to_str() {
+        Some(name) => {
+            let name = name.to_ascii_lowercase();
+            EXTENSIONS
+                .iter()
+                .any(|ext| name.len() > ext.len() && name.ends_with(ext))
+        }
+        None => false,
+    }
+}
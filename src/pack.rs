@@ -0,0 +1,270 @@
+//! Writes the contents of a store into a new archive.
+//!
+//! ```
+//! # fn main() -> std::io::Result<()> {
+//! use mini_fs::prelude::*;
+//! use mini_fs::{pack, RamFs, ZipFs};
+//! use std::io::Cursor;
+//!
+//! let mut ram = RamFs::new();
+//! ram.touch("gfx/a.png", b"a".to_vec());
+//! ram.touch("gfx/b.psd", b"b".to_vec());
+//!
+//! let options = pack::Options::new().filter(|path, _| path.extension() != Some("psd".as_ref()));
+//! let zip = pack::to_zip(&ram, "gfx", Cursor::new(Vec::new()), options)?;
+//!
+//! let zip = ZipFs::new(Cursor::new(zip.into_inner()));
+//! assert!(zip.open("a.png").is_ok());
+//! assert!(zip.open("b.psd").is_err());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::index::normalize_path;
+use crate::store::{Entry, EntryKind, Metadata, Store};
+use crate::walk::Walk;
+
+type FilterFn<'a> = Box<dyn FnMut(&Path, &Entry) -> bool + 'a>;
+
+/// Options of the archives written by [`to_zip`] and [`to_tar`].
+///
+/// [`to_zip`]: ./fn.to_zip.html
+/// [`to_tar`]: ./fn.to_tar.html
+pub struct Options<'a> {
+    stored: bool,
+    gzip: Option<u32>,
+    filter: Option<FilterFn<'a>>,
+}
+
+impl Default for Options<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Options<'a> {
+    /// Creates the default options: deflated zip entries, and uncompressed
+    /// tar archives.
+    pub fn new() -> Self {
+        Self {
+            stored: false,
+            gzip: None,
+            filter: None,
+        }
+    }
+
+    /// Stores zip entries without compressing them.
+    pub fn stored(mut self) -> Self {
+        self.stored = true;
+        self
+    }
+
+    /// Compresses tar archives with gzip, at a level from 0 (no compression)
+    /// to 9 (best compression).
+    pub fn gzip(mut self, level: u32) -> Self {
+        self.gzip = Some(level.min(9));
+        self
+    }
+
+    /// Only packs the entries for which the predicate returns true. The
+    /// predicate gets the path of the entry in the archive. The contents of
+    /// skipped directories are not visited.
+    pub fn filter<F>(mut self, predicate: F) -> Self
+    where
+        F: FnMut(&Path, &Entry) -> bool + 'a,
+    {
+        self.filter = Some(Box::new(predicate));
+        self
+    }
+}
+
+// Entry to be written into the archive.
+struct Item {
+    // path in the store
+    path: PathBuf,
+    // path in the archive
+    name: PathBuf,
+    entry: Entry,
+    meta: Option<Metadata>,
+}
+
+// Walks the store under `root`, calling `f` with each entry that passes the
+// filter.
+fn walk<S, F>(store: &S, root: &Path, options: &mut Options, mut f: F) -> io::Result<()>
+where
+    S: Store + ?Sized,
+    F: FnMut(Item) -> io::Result<()>,
+{
+    let root = normalize_path(root);
+    let mut walk = Walk::new(store, root.to_path_buf()).sort_by(|a, b| a.name.cmp(&b.name));
+    while let Some(next) = walk.next() {
+        let (path, entry) = next?;
+        let name = match path.strip_prefix(&root) {
+            Ok(name) => name.to_path_buf(),
+            Err(_) => path.clone(),
+        };
+        if let Some(ref mut filter) = options.filter {
+            if !filter(&name, &entry) {
+                walk.skip_current_dir();
+                continue;
+            }
+        }
+        // not every store has metadata
+        let meta = store.metadata_path(&path).ok();
+        f(Item {
+            path,
+            name,
+            entry,
+            meta,
+        })?;
+    }
+    Ok(())
+}
+
+// Opens a file of the store, along with its length.
+fn open<S>(store: &S, path: &Path) -> io::Result<(S::File, u64)>
+where
+    S: Store + ?Sized,
+    S::File: Read + Seek,
+{
+    let mut file = store.open_path(path)?;
+    let len = file.seek(SeekFrom::End(0))?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok((file, len))
+}
+
+/// Writes the files and directories under `root` into a zip archive.
+///
+/// Modification times and Unix permissions are kept when the store has them.
+/// Zip archives can't hold symbolic links: links to files are written as
+/// copies of their target, and links to directories are skipped.
+///
+/// Returns the writer, positioned after the end of the archive.
+#[cfg(feature = "zip")]
+pub fn to_zip<S, P, W>(store: &S, root: P, writer: W, mut options: Options) -> io::Result<W>
+where
+    S: Store + ?Sized,
+    S::File: Read + Seek,
+    P: AsRef<Path>,
+    W: Write + Seek,
+{
+    use zip_::write::FileOptions;
+    use zip_::{CompressionMethod, ZipWriter};
+
+    let method = if options.stored {
+        CompressionMethod::Stored
+    } else {
+        CompressionMethod::Deflated
+    };
+    let mut zip = ZipWriter::new(writer);
+    walk(store, root.as_ref(), &mut options, |item| {
+        let mut opts = FileOptions::default().compression_method(method);
+        if let Some(ref meta) = item.meta {
+            if let Some(time) = meta.modified.and_then(crate::zip::dos_time) {
+                opts = opts.last_modified_time(time);
+            }
+            if let Some(mode) = meta.permissions {
+                opts = opts.unix_permissions(mode);
+            }
+        }
+        let name = item
+            .name
+            .to_str()
+            .ok_or_else(|| io::Error::other("Utf8 path conversion error."))?;
+        let is_dir = match item.entry.kind {
+            EntryKind::Dir => true,
+            // the metadata of links describes their target
+            EntryKind::Symlink => item.meta.as_ref().is_none_or(Metadata::is_dir),
+            EntryKind::File => false,
+        };
+        if is_dir {
+            if item.entry.kind == EntryKind::Dir {
+                zip.add_directory(name, opts)?;
+            }
+            return Ok(());
+        }
+        let (mut file, len) = open(store, &item.path)?;
+        zip.start_file(name, opts.large_file(len > u64::from(u32::MAX)))?;
+        io::copy(&mut file, &mut zip)?;
+        Ok(())
+    })?;
+    Ok(zip.finish()?)
+}
+
+/// Writes the files, directories and symbolic links under `root` into a tar
+/// archive, compressed with gzip if the options say so.
+///
+/// Modification times and Unix permissions are kept when the store has them.
+///
+/// Returns the writer, positioned after the end of the archive.
+#[cfg(feature = "tar")]
+pub fn to_tar<S, P, W>(store: &S, root: P, writer: W, mut options: Options) -> io::Result<W>
+where
+    S: Store + ?Sized,
+    S::File: Read + Seek,
+    P: AsRef<Path>,
+    W: Write,
+{
+    use flate2::write::GzEncoder;
+
+    match options.gzip {
+        Some(level) => {
+            let gzip = GzEncoder::new(writer, flate2::Compression::new(level));
+            write_tar(store, root.as_ref(), gzip, &mut options)?.finish()
+        }
+        None => write_tar(store, root.as_ref(), writer, &mut options),
+    }
+}
+
+#[cfg(feature = "tar")]
+fn write_tar<S, W>(store: &S, root: &Path, writer: W, options: &mut Options) -> io::Result<W>
+where
+    S: Store + ?Sized,
+    S::File: Read + Seek,
+    W: Write,
+{
+    use std::time::UNIX_EPOCH;
+    use tar_::{Builder, EntryType, Header};
+
+    let mut tar = Builder::new(writer);
+    walk(store, root, options, |item| {
+        // links are written as links, so their metadata is that of the target
+        let link = item.entry.kind == EntryKind::Symlink;
+        let meta = item.meta.filter(|_| !link);
+        let mut header = Header::new_gnu();
+        let mtime = meta
+            .as_ref()
+            .and_then(|meta| meta.modified)
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |time| time.as_secs());
+        header.set_mtime(mtime);
+        let mode = meta.as_ref().and_then(|meta| meta.permissions);
+
+        match item.entry.kind {
+            EntryKind::Dir => {
+                header.set_entry_type(EntryType::Directory);
+                header.set_mode(mode.unwrap_or(0o755));
+                header.set_size(0);
+                tar.append_data(&mut header, &item.name, io::empty())
+            }
+            EntryKind::Symlink => {
+                let target = store.read_link_path(&item.path)?;
+                header.set_entry_type(EntryType::Symlink);
+                header.set_mode(0o777);
+                header.set_size(0);
+                tar.append_link(&mut header, &item.name, target)
+            }
+            EntryKind::File => {
+                let (file, len) = open(store, &item.path)?;
+                header.set_entry_type(EntryType::Regular);
+                header.set_mode(mode.unwrap_or(0o644));
+                header.set_size(len);
+                tar.append_data(&mut header, &item.name, file)
+            }
+        }
+    })?;
+    tar.into_inner()
+}
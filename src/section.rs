@@ -0,0 +1,88 @@
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
+
+/// Reader shared between an archive store and the files it opens.
+pub(crate) type Shared<R> = Arc<Mutex<R>>;
+
+/// Reader of any type, for the files that are converted into a `File`.
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek + ?Sized> ReadSeek for T {}
+
+/// Read-only view over a contiguous region of a shared reader.
+///
+/// The inner reader is only locked for the duration of each read, so many
+/// sections over the same reader can be alive at once, even across threads.
+pub(crate) struct Section<R: ?Sized = dyn ReadSeek + Send> {
+    inner: Shared<R>,
+    start: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl<R: ?Sized> Section<R> {
+    pub(crate) fn new(inner: Shared<R>, start: u64, len: u64) -> Self {
+        Self {
+            inner,
+            start,
+            len,
+            pos: 0,
+        }
+    }
+
+    /// Returns a new section over the same region, positioned at the start.
+    #[cfg(feature = "zip")]
+    pub(crate) fn rewind(&self) -> Self {
+        Self::new(Arc::clone(&self.inner), self.start, self.len)
+    }
+}
+
+impl<R: ReadSeek + Send + 'static> Section<R> {
+    /// Hides the type of the inner reader.
+    pub(crate) fn erase(self) -> Section {
+        Section {
+            inner: self.inner,
+            start: self.start,
+            len: self.len,
+            pos: self.pos,
+        }
+    }
+}
+
+impl<R: ReadSeek + ?Sized> Read for Section<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len {
+            return Ok(0);
+        }
+        let max = (self.len - self.pos).min(buf.len() as u64) as usize;
+        let mut inner = self.inner.lock().unwrap();
+        inner.seek(SeekFrom::Start(self.start + self.pos))?;
+        let n = inner.read(&mut buf[..max])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: ?Sized> Seek for Section<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = seek_position(self.pos, self.len, pos)?;
+        Ok(self.pos)
+    }
+}
+
+/// Computes the position resulting from a seek, given the current position
+/// and the length of the stream.
+pub(crate) fn seek_position(current: u64, len: u64, pos: SeekFrom) -> io::Result<u64> {
+    let (base, offset) = match pos {
+        SeekFrom::Start(n) => return Ok(n),
+        SeekFrom::End(n) => (len, n),
+        SeekFrom::Current(n) => (current, n),
+    };
+    match base.checked_add_signed(offset) {
+        Some(n) => Ok(n),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid seek to a negative or overflowing position",
+        )),
+    }
+}
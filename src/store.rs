@@ -1,7 +1,11 @@
 use std::collections::btree_set::BTreeSet;
-use std::ffi::OsString;
-use std::io;
-use std::path::Path;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::glob::{Glob, Pattern};
+use crate::walk::{file_name, Walk};
 
 /// File or directory entry.
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -15,8 +19,69 @@ pub struct Entry {
 pub enum EntryKind {
     File,
     Dir,
-    /*TODO symlinks
-     *Sym, */
+    Symlink,
+}
+
+/// How symbolic links are resolved when opening files and reading metadata.
+///
+/// Listings always report symbolic links as `EntryKind::Symlink`, whatever the
+/// policy.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum LinkPolicy {
+    /// Follow symbolic links. In archives, absolute targets are resolved from
+    /// the root of the archive.
+    #[default]
+    Follow,
+    /// Don't follow symbolic links. Opening a link is an error, and its
+    /// metadata describes the link itself.
+    NoFollow,
+    /// Follow symbolic links, as long as their targets are in the store.
+    /// Links that point outside of it are rejected with a `PermissionDenied`
+    /// error.
+    FollowWithin,
+}
+
+/// File or directory metadata.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Metadata {
+    pub kind: EntryKind,
+    /// Size of the file in bytes. Zero for directories.
+    pub len: u64,
+    /// Last modification time, if known.
+    pub modified: Option<SystemTime>,
+    /// Unix permission bits, if known.
+    pub permissions: Option<u32>,
+    /// CRC-32 checksum of the file contents, if known.
+    pub crc32: Option<u32>,
+}
+
+impl Metadata {
+    pub(crate) fn dir() -> Self {
+        Self {
+            kind: EntryKind::Dir,
+            len: 0,
+            modified: None,
+            permissions: None,
+            crc32: None,
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Dir
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.kind == EntryKind::File
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.kind == EntryKind::Symlink
+    }
+}
+
+// Error returned when opening a symbolic link that isn't followed.
+pub(crate) fn link_error() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "path is a symbolic link")
 }
 
 /// Iterator of file entries.
@@ -51,24 +116,186 @@ pub trait Store {
     fn open_path(&self, path: &Path) -> io::Result<Self::File>;
 
     /// Returns an iterator over the files & directory entries in a given path.
-    fn entries_path(&self, _: &Path) -> io::Result<Entries> {
-        unimplemented!("entries_path is not implemented.")
+    ///
+    /// Stores that can't list their contents return an error of kind
+    /// `ErrorKind::Unsupported`, which is also what the default implementation
+    /// does.
+    fn entries_path(&self, _: &Path) -> io::Result<Entries<'_>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "entries_path is not implemented.",
+        ))
+    }
+
+    /// Returns the metadata of the file or directory in a given path.
+    fn metadata_path(&self, _: &Path) -> io::Result<Metadata> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "metadata_path is not implemented.",
+        ))
+    }
+
+    /// Returns the target of the symbolic link in a given path.
+    fn read_link_path(&self, _: &Path) -> io::Result<PathBuf> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "read_link_path is not implemented.",
+        ))
+    }
+
+    /// Returns an iterator over the files & directories that match a glob
+    /// pattern.
+    ///
+    /// The default implementation lists the directories that can contain
+    /// matches using `entries_path`.
+    fn glob_path(&self, pattern: &Pattern) -> io::Result<Glob<'_>> {
+        Ok(Glob::walk(self, pattern.clone()))
     }
 }
 
 /// Convenient methods on top of Store.
 pub trait StoreExt: Store {
-    fn entries<P: AsRef<Path>>(&self, path: P) -> io::Result<Entries> {
+    fn entries<P: AsRef<Path>>(&self, path: P) -> io::Result<Entries<'_>> {
         <Self as Store>::entries_path(self, &crate::index::normalize_path(path.as_ref()))
     }
 
     fn open<P: AsRef<Path>>(&self, path: P) -> io::Result<Self::File> {
         <Self as Store>::open_path(self, &crate::index::normalize_path(path.as_ref()))
     }
+
+    fn metadata<P: AsRef<Path>>(&self, path: P) -> io::Result<Metadata> {
+        <Self as Store>::metadata_path(self, &crate::index::normalize_path(path.as_ref()))
+    }
+
+    fn read_link<P: AsRef<Path>>(&self, path: P) -> io::Result<PathBuf> {
+        <Self as Store>::read_link_path(self, &crate::index::normalize_path(path.as_ref()))
+    }
+
+    /// Returns an iterator that recursively walks the files & directories
+    /// under a given path.
+    ///
+    /// ```
+    /// use mini_fs::prelude::*;
+    /// use mini_fs::RamFs;
+    /// use std::path::Path;
+    ///
+    /// let mut ram = RamFs::new();
+    /// ram.touch("a/b.txt", b"b".to_vec());
+    /// ram.touch("a/c/d.txt", b"d".to_vec());
+    ///
+    /// let paths = ram
+    ///     .walk("a")
+    ///     .sort_by(|a, b| a.name.cmp(&b.name))
+    ///     .map(|e| e.map(|(path, _)| path))
+    ///     .collect::<std::io::Result<Vec<_>>>()?;
+    ///
+    /// let expected = ["a/b.txt", "a/c", "a/c/d.txt"].iter().map(Path::new);
+    /// assert!(expected.eq(paths));
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    fn walk<P: AsRef<Path>>(&self, path: P) -> Walk<'_, Self> {
+        Walk::new(
+            self,
+            crate::index::normalize_path(path.as_ref()).into_owned(),
+        )
+    }
+
+    /// Returns an iterator over the files & directories that match a glob
+    /// pattern. See [`Pattern`] for the syntax.
+    ///
+    /// ```
+    /// use mini_fs::prelude::*;
+    /// use mini_fs::RamFs;
+    ///
+    /// let mut ram = RamFs::new();
+    /// ram.touch("gfx/a.png", b"a".to_vec());
+    /// ram.touch("gfx/ui/b.png", b"b".to_vec());
+    /// ram.touch("sfx/c.wav", b"c".to_vec());
+    ///
+    /// assert_eq!(2, ram.glob("**/*.png")?.count());
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    ///
+    /// [`Pattern`]: ./struct.Pattern.html
+    fn glob(&self, pattern: &str) -> io::Result<Glob<'_>> {
+        <Self as Store>::glob_path(self, &Pattern::new(pattern)?)
+    }
 }
 
 impl<T: Store> StoreExt for T {}
 
+/// Generic file storage that can be modified.
+pub trait StoreMut: Store {
+    type FileMut: Write;
+
+    /// Opens a file in write mode, creating it if it doesn't exist, and
+    /// truncating it if it does.
+    fn create_path(&self, path: &Path) -> io::Result<Self::FileMut>;
+
+    /// Writes the entire contents of a file, creating it if it doesn't exist.
+    fn write_path(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        let mut file = self.create_path(path)?;
+        file.write_all(data)?;
+        file.flush()
+    }
+
+    /// Removes a file.
+    fn remove_file_path(&self, path: &Path) -> io::Result<()>;
+
+    /// Removes an empty directory.
+    fn remove_dir_path(&self, path: &Path) -> io::Result<()>;
+
+    /// Removes a directory along with all of its contents.
+    fn remove_dir_all_path(&self, path: &Path) -> io::Result<()>;
+
+    /// Creates a directory, along with any missing parent directories.
+    fn create_dir_all_path(&self, path: &Path) -> io::Result<()>;
+
+    /// Renames a file or directory, replacing the destination if it exists.
+    fn rename_path(&self, from: &Path, to: &Path) -> io::Result<()>;
+}
+
+/// Convenient methods on top of StoreMut.
+pub trait StoreMutExt: StoreMut {
+    fn create<P: AsRef<Path>>(&self, path: P) -> io::Result<Self::FileMut> {
+        <Self as StoreMut>::create_path(self, &crate::index::normalize_path(path.as_ref()))
+    }
+
+    fn write<P: AsRef<Path>, D: AsRef<[u8]>>(&self, path: P, data: D) -> io::Result<()> {
+        <Self as StoreMut>::write_path(
+            self,
+            &crate::index::normalize_path(path.as_ref()),
+            data.as_ref(),
+        )
+    }
+
+    fn remove_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        <Self as StoreMut>::remove_file_path(self, &crate::index::normalize_path(path.as_ref()))
+    }
+
+    fn remove_dir<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        <Self as StoreMut>::remove_dir_path(self, &crate::index::normalize_path(path.as_ref()))
+    }
+
+    fn remove_dir_all<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        <Self as StoreMut>::remove_dir_all_path(self, &crate::index::normalize_path(path.as_ref()))
+    }
+
+    fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        <Self as StoreMut>::create_dir_all_path(self, &crate::index::normalize_path(path.as_ref()))
+    }
+
+    fn rename<P: AsRef<Path>, Q: AsRef<Path>>(&self, from: P, to: Q) -> io::Result<()> {
+        <Self as StoreMut>::rename_path(
+            self,
+            &crate::index::normalize_path(from.as_ref()),
+            &crate::index::normalize_path(to.as_ref()),
+        )
+    }
+}
+
+impl<T: StoreMut> StoreMutExt for T {}
+
 pub(crate) struct MapFile<S, F> {
     store: S,
     clo: F,
@@ -98,45 +325,160 @@ where
     }
 
     #[inline]
-    fn entries_path(&self, path: &Path) -> io::Result<Entries> {
+    fn entries_path(&self, path: &Path) -> io::Result<Entries<'_>> {
         self.store.entries_path(path)
     }
+
+    #[inline]
+    fn metadata_path(&self, path: &Path) -> io::Result<Metadata> {
+        self.store.metadata_path(path)
+    }
+
+    #[inline]
+    fn read_link_path(&self, path: &Path) -> io::Result<PathBuf> {
+        self.store.read_link_path(path)
+    }
+
+    #[inline]
+    fn glob_path(&self, pattern: &Pattern) -> io::Result<Glob<'_>> {
+        self.store.glob_path(pattern)
+    }
 }
 
-// iterator + set to take care of repeating elements.
-// TODO consider other data structures for the set.
-struct TupleEntries<I> {
-    inner: I,
-    set: BTreeSet<OsString>,
+/// Boxed stores, such as the ones returned by [`open_archive`], are stores too.
+///
+/// [`open_archive`]: ./fn.open_archive.html
+impl<S> Store for Box<S>
+where
+    S: Store + ?Sized,
+{
+    type File = S::File;
+
+    #[inline]
+    fn open_path(&self, path: &Path) -> io::Result<Self::File> {
+        (**self).open_path(path)
+    }
+
+    #[inline]
+    fn entries_path(&self, path: &Path) -> io::Result<Entries<'_>> {
+        (**self).entries_path(path)
+    }
+
+    #[inline]
+    fn metadata_path(&self, path: &Path) -> io::Result<Metadata> {
+        (**self).metadata_path(path)
+    }
+
+    #[inline]
+    fn read_link_path(&self, path: &Path) -> io::Result<PathBuf> {
+        (**self).read_link_path(path)
+    }
+
+    #[inline]
+    fn glob_path(&self, pattern: &Pattern) -> io::Result<Glob<'_>> {
+        (**self).glob_path(pattern)
+    }
 }
 
-impl<I> TupleEntries<I> {
-    fn new(inner: I) -> Self {
-        Self {
-            inner,
-            set: BTreeSet::new(),
+/// Prefix of the marker files that hide entries of the stores below them in
+/// an overlay.
+const WHITEOUT: &str = ".wh.";
+/// Marker file that hides the contents of a directory in the stores below.
+const OPAQUE: &str = ".wh..wh..opq";
+
+/// Returns true if the name is that of a whiteout marker.
+fn is_whiteout(name: &OsStr) -> bool {
+    name.to_str().is_some_and(|name| name.starts_with(WHITEOUT))
+}
+
+/// Returns true if the last component of the path is a whiteout marker, which
+/// overlays never expose.
+fn is_marker(path: &Path) -> bool {
+    path.file_name().is_some_and(is_whiteout)
+}
+
+/// Returns true if the store has a file in the given path. Falls back to
+/// opening the file in stores without metadata.
+fn exists<S: Store + ?Sized>(store: &S, path: &Path) -> bool {
+    match store.metadata_path(path) {
+        Ok(_) => true,
+        Err(ref err) if err.kind() == io::ErrorKind::Unsupported => store.open_path(path).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Looks for an opaque marker, or the given whiteout, in a directory of the
+/// store. Stores that can't list directories are asked for each marker.
+/// Returns None if the directory doesn't exist.
+fn marked<S: Store + ?Sized>(store: &S, dir: &Path, whiteout: &OsStr) -> Option<bool> {
+    match store.entries_path(dir) {
+        Ok(mut entries) => Some(entries.any(|entry| {
+            entry.is_ok_and(|entry| {
+                let name = file_name(&entry);
+                name == OPAQUE || name == whiteout
+            })
+        })),
+        Err(ref err) if err.kind() == io::ErrorKind::Unsupported => {
+            Some(exists(store, &dir.join(OPAQUE)) || exists(store, &dir.join(whiteout)))
         }
+        Err(_) => None,
     }
 }
 
-impl<I> Iterator for TupleEntries<I>
-where
-    I: Iterator<Item = io::Result<Entry>>,
-{
-    type Item = <I as Iterator>::Item;
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            match self.inner.next() {
-                None => return None,
-                Some(Err(e)) => return Some(Err(e)),
-                Some(Ok(file)) => {
-                    if self.set.insert(file.name.clone()) {
-                        return Some(Ok(file));
-                    }
-                }
+/// Returns true if the store hides the path from the stores below it, either
+/// with a whiteout of the path or one of its parents, or with an opaque parent
+/// directory. Each parent is listed once, and the search stops at the first
+/// one missing from the store.
+fn hides<S: Store + ?Sized>(store: &S, path: &Path) -> bool {
+    let mut dir = PathBuf::new();
+    for component in path.components() {
+        if let Component::Normal(name) = component {
+            let mut marker = OsString::from(WHITEOUT);
+            marker.push(name);
+            match marked(store, &dir, &marker) {
+                Some(true) => return true,
+                Some(false) => {}
+                None => return false,
             }
         }
+        dir.push(component);
     }
+    false
+}
+
+/// Lists a directory of a store as a layer of an overlay, and adds it to
+/// `layers`. Returns true if the store hides the contents of the directory
+/// from the stores below it.
+///
+/// The markers are taken from the listing itself, so only the parents of the
+/// directory are listed again to look for whiteouts.
+fn push_layer<S: Store + ?Sized>(
+    layers: &mut Vec<Layer>,
+    store: &S,
+    path: &Path,
+) -> io::Result<bool> {
+    let mut entries = Vec::new();
+    let mut whiteouts = Vec::new();
+    let mut opaque = false;
+    for entry in store.entries_path(path)? {
+        if let Ok(ref entry) = entry {
+            let name = file_name(entry);
+            if name == OPAQUE {
+                opaque = true;
+                continue;
+            }
+            if is_whiteout(name) {
+                whiteouts.push(name.to_str().unwrap()[WHITEOUT.len()..].into());
+                continue;
+            }
+        }
+        entries.push(entry);
+    }
+    layers.push(Layer {
+        entries: entries.into_iter(),
+        whiteouts,
+    });
+    Ok(opaque || hides(store, path))
 }
 
 // Implement tuples of up to 11 elements (12 or more looks bad on the rustdoc)
@@ -144,6 +486,12 @@ store_tuples! { A, B, C, D, E, F, G, H, I, J, K, }
 
 /// A vector of stores can be used as an overlay filesystem.
 /// Naturally, all the stores will have the same type.
+///
+/// Stores can hide the entries of the stores that come after them with
+/// overlayfs-style whiteout markers: a `.wh.<name>` file hides `<name>` (and
+/// everything under it), and a `.wh..wh..opq` file hides the contents of the
+/// directory it is in. The markers themselves are never exposed. Tuples of
+/// stores follow the same rules.
 impl<S> Store for Vec<S>
 where
     S: Store,
@@ -152,63 +500,120 @@ where
 
     /// Opens the file identified by path.
     fn open_path(&self, path: &Path) -> io::Result<Self::File> {
-        for store in self {
-            match store.open_path(&path) {
-                Ok(file) => return Ok(file),
-                Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
-                Err(err) => return Err(err),
+        if !is_marker(path) {
+            for store in self {
+                match store.open_path(path) {
+                    Ok(file) => return Ok(file),
+                    Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+                    Err(err) => return Err(err),
+                }
+                if hides(store, path) {
+                    break;
+                }
             }
         }
-        return Err(io::ErrorKind::NotFound.into());
+        Err(io::ErrorKind::NotFound.into())
     }
 
     /// Returns an iterator over the entries.
-    /// Skips duplicate entries.
-    fn entries_path(&self, path: &Path) -> io::Result<Entries> {
-        let mut iterators = Vec::with_capacity(self.capacity());
+    /// Skips duplicate and hidden entries.
+    fn entries_path(&self, path: &Path) -> io::Result<Entries<'_>> {
+        let mut layers = Vec::with_capacity(self.len());
         for store in self.iter() {
-            iterators.push(store.entries_path(path)?);
+            if push_layer(&mut layers, store, path)? {
+                break;
+            }
         }
-        Ok(Entries::new(VecEntries {
-            inner: iterators,
-            set: BTreeSet::new(),
-        }))
+        Ok(Entries::new(OverlayEntries::new(layers)))
+    }
+
+    /// Returns the metadata of the first store that contains the path.
+    fn metadata_path(&self, path: &Path) -> io::Result<Metadata> {
+        if !is_marker(path) {
+            for store in self {
+                match store.metadata_path(path) {
+                    Ok(meta) => return Ok(meta),
+                    Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+                    Err(err) => return Err(err),
+                }
+                if hides(store, path) {
+                    break;
+                }
+            }
+        }
+        Err(io::ErrorKind::NotFound.into())
+    }
+
+    /// Reads the link from the first store that contains the path.
+    fn read_link_path(&self, path: &Path) -> io::Result<PathBuf> {
+        if !is_marker(path) {
+            for store in self {
+                match store.read_link_path(path) {
+                    Ok(target) => return Ok(target),
+                    Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+                    Err(err) => return Err(err),
+                }
+                if hides(store, path) {
+                    break;
+                }
+            }
+        }
+        Err(io::ErrorKind::NotFound.into())
     }
 }
 
-/// Iterator over the entries of the inner stores that skips duplicates.
-struct VecEntries<I> {
-    /// Inner iterators.
-    inner: Vec<I>,
+/// Entries of a directory in one of the layers of an overlay, without the
+/// whiteout markers.
+struct Layer {
+    entries: std::vec::IntoIter<io::Result<Entry>>,
+    /// Names hidden from the layers below.
+    whiteouts: Vec<OsString>,
+}
+
+/// Iterator over the entries of the layers of an overlay that skips
+/// duplicates and the entries hidden by whiteouts.
+struct OverlayEntries {
+    /// Layers, from the top to the bottom one.
+    layers: Vec<Layer>,
     /// Set identifying the entries that have been returned.
     /// Used to skip duplicates.
     set: BTreeSet<OsString>,
+    /// Names hidden by the whiteouts of the layers above the current one.
+    hidden: BTreeSet<OsString>,
 }
 
-impl<I> Iterator for VecEntries<I>
-where
-    I: Iterator<Item = io::Result<Entry>>,
-{
-    type Item = I::Item;
+impl OverlayEntries {
+    fn new(layers: Vec<Layer>) -> Self {
+        Self {
+            layers,
+            set: BTreeSet::new(),
+            hidden: BTreeSet::new(),
+        }
+    }
+}
+
+impl Iterator for OverlayEntries {
+    type Item = io::Result<Entry>;
 
     /// Gets the next entry result or None.
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            if self.inner.len() == 0 {
-                return None; // no more entries
-            }
-            while let Some(result) = self.inner[0].next() {
+            let layer = self.layers.first_mut()?;
+            for result in &mut layer.entries {
                 match result {
                     Err(err) => return Some(Err(err)),
                     Ok(entry) => {
-                        // skip duplicate entries
-                        if self.set.insert(entry.name.clone()) {
+                        // skip hidden and duplicate entries
+                        let name = file_name(&entry);
+                        if !self.hidden.contains(name) && self.set.insert(name.to_os_string()) {
                             return Some(Ok(entry));
                         }
                     }
                 }
             }
-            self.inner.remove(0); // iterator is done, try next
+            // layer is done, try the next one
+            let layer = self.layers.remove(0);
+            self.hidden.extend(layer.whiteouts);
         }
     }
 }
@@ -1,14 +1,16 @@
-use std::cell::{Cell, RefCell};
 use std::fs;
 use std::io::{self, Cursor, ErrorKind, Read, Seek, SeekFrom};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, UNIX_EPOCH};
 
 use flate2::read::GzDecoder;
-use tar_::Archive;
+use tar_::{Archive, EntryType};
 
 use crate::index::Index;
-use crate::store::Store;
-use crate::{Entries, Entry};
+use crate::section::{seek_position, ReadSeek, Section, Shared};
+use crate::store::{self, Store};
+use crate::{Entries, Entry, EntryKind, File, Glob, LinkPolicy, Metadata, Pattern};
 
 /// Tar archive.
 ///
@@ -16,64 +18,393 @@ use crate::{Entries, Entry};
 ///
 /// When used with a `std::fs::File`, the file will remain open for the lifetime
 /// of the Tar.
+///
+/// Opened files are read straight from the archive. Compressed archives are
+/// decompressed in memory the first time they are read, and the decompressed
+/// copy is shared by subsequent reads. They need as much memory as their
+/// decompressed size, so large compressed archives are better read with
+/// [`streaming`](#method.streaming).
+///
+/// The compression is detected from the first bytes of the archive, unless it
+/// is set with [`compression`](#method.compression).
+///
+/// The archive is indexed in a single pass the first time it is used. When a
+/// path appears more than once, the last entry wins, as when extracting.
 pub struct TarFs<F: Read + Seek> {
-    gzip: Cell<bool>,
-    inner: RefCell<F>,
-    index: Option<Index<SeekFrom>>,
+    compression: OnceLock<Compression>,
+    inner: Arc<Mutex<F>>,
+    cache: Mutex<Option<Shared<Cursor<Vec<u8>>>>>,
+    index: OnceLock<Index<TarEntry>>,
+    streaming: bool,
+    links: LinkPolicy,
+}
+
+/// Compression of a tar archive.
+///
+/// Gzip is always supported. The other codecs need the cargo feature of the
+/// same name (`bzip2`, `xz` and `zstd`), and reading archives compressed with
+/// a disabled codec fails with an `Unsupported` error.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Compression {
+    /// Raw tar archive.
+    None,
+    /// Gzip (`.tar.gz`).
+    Gzip,
+    /// Bzip2 (`.tar.bz2`).
+    Bzip2,
+    /// Xz (`.tar.xz`).
+    Xz,
+    /// Zstandard (`.tar.zst`).
+    Zstd,
+}
+
+impl Compression {
+    /// Detects the compression from the magic bytes at the start of an
+    /// archive. Anything else is assumed to be a raw tar archive.
+    pub fn detect(magic: &[u8]) -> Self {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if magic.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    // Decompresses the whole stream.
+    fn decode(self, read: &mut dyn Read) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.decoder(read)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    // Wraps a reader to decompress the stream as it is read.
+    pub(crate) fn decoder<R: Read>(self, read: R) -> io::Result<Decoder<R>> {
+        Ok(match self {
+            Compression::None => Decoder::None(read),
+            Compression::Gzip => Decoder::Gzip(GzDecoder::new(read)),
+            #[cfg(feature = "bzip2")]
+            Compression::Bzip2 => Decoder::Bzip2(bzip2_::read::BzDecoder::new(read)),
+            #[cfg(feature = "xz")]
+            Compression::Xz => Decoder::Xz(xz2::bufread::XzDecoder::new(io::BufReader::new(read))),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Decoder::Zstd(zstd_::stream::read::Decoder::new(read)?),
+            #[allow(unreachable_patterns)]
+            codec => {
+                return Err(io::Error::new(
+                    ErrorKind::Unsupported,
+                    format!("{:?} support is not enabled", codec),
+                ))
+            }
+        })
+    }
+}
+
+/// Reader that decompresses a stream with one of the codecs.
+pub(crate) enum Decoder<R> {
+    None(R),
+    Gzip(GzDecoder<R>),
+    #[cfg(feature = "bzip2")]
+    Bzip2(bzip2_::read::BzDecoder<R>),
+    #[cfg(feature = "xz")]
+    Xz(xz2::bufread::XzDecoder<io::BufReader<R>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd_::stream::read::Decoder<'static, io::BufReader<R>>),
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Decoder::None(read) => read.read(buf),
+            Decoder::Gzip(read) => read.read(buf),
+            #[cfg(feature = "bzip2")]
+            Decoder::Bzip2(read) => read.read(buf),
+            #[cfg(feature = "xz")]
+            Decoder::Xz(read) => read.read(buf),
+            #[cfg(feature = "zstd")]
+            Decoder::Zstd(read) => read.read(buf),
+        }
+    }
+}
+
+/// Location of the data of an entry in the (decompressed) tar stream, along
+/// with the metadata from its header.
+#[derive(Debug, Clone)]
+struct TarEntry {
+    offset: u64,
+    size: u64,
+    mtime: Option<u64>,
+    mode: Option<u32>,
+    link: Option<TarLink>,
+}
+
+#[derive(Debug, Clone)]
+enum TarLink {
+    // Target relative to the directory of the link.
+    Symbolic(PathBuf),
+    // Path of an earlier entry of the archive.
+    Hard(PathBuf),
+}
+
+impl TarEntry {
+    fn new<R: Read>(entry: &tar_::Entry<R>) -> io::Result<Self> {
+        let header = entry.header();
+        let link = match (header.entry_type(), entry.link_name()?) {
+            (EntryType::Symlink, Some(target)) => Some(TarLink::Symbolic(target.into_owned())),
+            (EntryType::Link, Some(target)) => Some(TarLink::Hard(target.into_owned())),
+            _ => None,
+        };
+        Ok(Self {
+            offset: entry.raw_file_position(),
+            size: entry.size(),
+            mtime: header.mtime().ok(),
+            mode: header.mode().ok(),
+            link,
+        })
+    }
+
+    fn symlink(&self) -> Option<&Path> {
+        match self.link {
+            Some(TarLink::Symbolic(ref target)) => Some(target),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> EntryKind {
+        match self.link {
+            Some(TarLink::Symbolic(_)) => EntryKind::Symlink,
+            _ => EntryKind::File,
+        }
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: self.kind(),
+            len: self.size,
+            modified: self.mtime.map(|s| UNIX_EPOCH + Duration::from_secs(s)),
+            permissions: self.mode.map(|m| m & 0o7777),
+            crc32: None,
+        }
+    }
 }
 
 /// Entry in the Tar archive.
-pub struct TarFsFile {
-    inner: Cursor<Box<[u8]>>,
+///
+/// Files of archives over any reader are converted into a [`File`] of the
+/// `Tar` variant, which hides the type of the reader.
+///
+/// [`File`]: ../enum.File.html
+pub struct TarFsFile<T: ?Sized = dyn ReadSeek + Send> {
+    inner: TarFsFileInner<T>,
+}
+
+enum TarFsFileInner<T: ?Sized> {
+    Raw(Section<T>),
+    Decompressed(Section<Cursor<Vec<u8>>>),
+    Streamed(Box<Streamed<T>>),
+}
+
+impl<T: ReadSeek + Send + 'static> From<TarFsFile<T>> for File {
+    fn from(file: TarFsFile<T>) -> Self {
+        let inner = match file.inner {
+            TarFsFileInner::Raw(file) => TarFsFileInner::Raw(file.erase()),
+            TarFsFileInner::Decompressed(file) => TarFsFileInner::Decompressed(file),
+            TarFsFileInner::Streamed(file) => TarFsFileInner::Streamed(Box::new(file.erase())),
+        };
+        File::Tar(TarFsFile { inner })
+    }
 }
 
-impl Read for TarFsFile {
+impl<T: ReadSeek + ?Sized> Read for TarFsFile<T> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner.read(buf)
+        match self.inner {
+            TarFsFileInner::Raw(ref mut file) => file.read(buf),
+            TarFsFileInner::Decompressed(ref mut file) => file.read(buf),
+            TarFsFileInner::Streamed(ref mut file) => file.read(buf),
+        }
     }
 }
 
-impl Seek for TarFsFile {
+impl<T: ReadSeek + ?Sized> Seek for TarFsFile<T> {
     #[inline]
     fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
-        self.inner.seek(pos)
+        match self.inner {
+            TarFsFileInner::Raw(ref mut file) => file.seek(pos),
+            TarFsFileInner::Decompressed(ref mut file) => file.seek(pos),
+            TarFsFileInner::Streamed(ref mut file) => file.seek(pos),
+        }
+    }
+}
+
+/// File of a compressed archive that is decompressed as it is read.
+///
+/// The archive is decompressed from its start up to the data of the file, and
+/// the data before it is discarded. Seeking backwards starts over.
+struct Streamed<T: ?Sized = dyn ReadSeek + Send> {
+    inner: Shared<T>,
+    compression: Compression,
+    // started by the first read
+    decoder: Option<Decoder<Section<T>>>,
+    // position of the data in the tar stream
+    start: u64,
+    size: u64,
+    // position of the decoder in the tar stream
+    offset: u64,
+    // position of the file
+    pos: u64,
+}
+
+impl<T: ?Sized> Streamed<T> {
+    fn new(inner: Shared<T>, compression: Compression, start: u64, size: u64) -> Self {
+        Self {
+            inner,
+            compression,
+            decoder: None,
+            start,
+            size,
+            offset: 0,
+            pos: 0,
+        }
+    }
+}
+
+impl<T: ReadSeek + Send + 'static> Streamed<T> {
+    fn erase(self) -> Streamed {
+        // the decoder can't change the type of its reader, so it starts over
+        Streamed {
+            inner: self.inner,
+            compression: self.compression,
+            decoder: None,
+            start: self.start,
+            size: self.size,
+            offset: 0,
+            pos: self.pos,
+        }
+    }
+}
+
+impl<T: ReadSeek + ?Sized> Read for Streamed<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.size {
+            return Ok(0);
+        }
+        let target = self.start + self.pos;
+        let decoder = match self.decoder {
+            Some(ref mut decoder) if self.offset <= target => decoder,
+            _ => {
+                let archive = Section::new(Arc::clone(&self.inner), 0, u64::MAX);
+                self.offset = 0;
+                self.decoder.insert(self.compression.decoder(archive)?)
+            }
+        };
+        if self.offset < target {
+            let skip = target - self.offset;
+            let skipped = io::copy(&mut (&mut *decoder).take(skip), &mut io::sink())?;
+            self.offset += skipped;
+            if skipped < skip {
+                return Err(ErrorKind::UnexpectedEof.into());
+            }
+        }
+        let max = (self.size - self.pos).min(buf.len() as u64) as usize;
+        let n = decoder.read(&mut buf[..max])?;
+        self.offset += n as u64;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<T: ?Sized> Seek for Streamed<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = seek_position(self.pos, self.size, pos)?;
+        Ok(self.pos)
+    }
+}
+
+/// Reader of the (decompressed) tar stream.
+enum Source<T> {
+    Raw(Shared<T>),
+    Decompressed(Shared<Cursor<Vec<u8>>>),
+    Streamed(Shared<T>, Compression),
+}
+
+impl<T> Source<T> {
+    // Returns the file over a region of the stream.
+    fn section(self, start: u64, len: u64) -> TarFsFile<T> {
+        let inner = match self {
+            Source::Raw(inner) => TarFsFileInner::Raw(Section::new(inner, start, len)),
+            Source::Decompressed(inner) => {
+                TarFsFileInner::Decompressed(Section::new(inner, start, len))
+            }
+            Source::Streamed(inner, compression) => {
+                TarFsFileInner::Streamed(Box::new(Streamed::new(inner, compression, start, len)))
+            }
+        };
+        TarFsFile { inner }
     }
 }
 
 impl<T: Read + Seek> Store for TarFs<T> {
-    type File = TarFsFile;
+    type File = TarFsFile<T>;
 
     fn open_path(&self, path: &Path) -> io::Result<Self::File> {
-        if self.gzip.get() {
-            let mut file = self.inner.borrow_mut();
-            file.seek(SeekFrom::Start(0))?;
-            self.open_read(path, GzDecoder::new(&mut *file))
-        } else {
-            let mut file = self.inner.borrow_mut();
-            file.seek(SeekFrom::Start(0))?;
-            match self.open_read(path, &mut *file) {
-                Ok(entry) => Ok(entry),
-                Err(ref e) if e.kind() == ErrorKind::NotFound => {
-                    Err(io::Error::from(ErrorKind::NotFound))
-                }
-                Err(_) => {
-                    self.gzip.set(true);
-                    drop(file);
-                    self.open_path(path)
-                }
-            }
+        let entry = match self.lookup(path)? {
+            Some(entry) => entry,
+            None => match self.resolve(path)? {
+                (_, Some(entry)) => entry,
+                (_, None) => return Err(io::Error::from(ErrorKind::NotFound)),
+            },
+        };
+        match entry.link {
+            None => Ok(self.source()?.section(entry.offset, entry.size)),
+            Some(TarLink::Symbolic(_)) => Err(store::link_error()),
+            // the target of the hard link is not in the archive
+            Some(TarLink::Hard(_)) => Err(io::Error::from(ErrorKind::NotFound)),
         }
     }
 
-    fn entries_path(&self, path: &Path) -> io::Result<Entries> {
-        if let Some(ref idx) = self.index {
-            Ok(Entries::new(idx.entries(path).map(|ent| {
-                let name = ent.name.to_os_string();
-                let kind = ent.kind;
-                Ok(Entry { name, kind })
-            })))
-        } else {
-            panic!("You have to call the `Zip::index` method on this zip archive before you can list its entries.")
+    fn entries_path(&self, path: &Path) -> io::Result<Entries<'_>> {
+        let idx = self.indexed()?;
+        Ok(Entries::new(idx.entries(path).map(|ent| {
+            let name = ent.name.to_os_string();
+            let kind = ent.meta.map_or(ent.kind, TarEntry::kind);
+            Ok(Entry { name, kind })
+        })))
+    }
+
+    fn glob_path(&self, pattern: &Pattern) -> io::Result<Glob<'_>> {
+        Ok(Glob::index(self.indexed()?, pattern, TarEntry::kind))
+    }
+
+    fn metadata_path(&self, path: &Path) -> io::Result<Metadata> {
+        if let Some(entry) = self.lookup(path)? {
+            return Ok(entry.metadata());
+        }
+        match self.resolve(path)? {
+            (_, Some(entry)) => Ok(entry.metadata()),
+            (ref real, None) if self.indexed()?.contains_dir(real) => Ok(Metadata::dir()),
+            (_, None) => Err(io::Error::from(ErrorKind::NotFound)),
+        }
+    }
+
+    fn read_link_path(&self, path: &Path) -> io::Result<PathBuf> {
+        let idx = self.indexed()?;
+        let path = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => idx
+                .resolve(parent, self.links, TarEntry::symlink)?
+                .join(name),
+            _ => path.to_path_buf(),
+        };
+        match idx.get(path).map(TarEntry::symlink) {
+            Some(Some(target)) => Ok(target.to_path_buf()),
+            Some(None) => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "path is not a symbolic link",
+            )),
+            None => Err(io::Error::from(ErrorKind::NotFound)),
         }
     }
 }
@@ -93,32 +424,161 @@ impl TarFs<fs::File> {
 impl<T: Read + Seek> TarFs<T> {
     pub fn new(inner: T) -> Self {
         Self {
-            inner: RefCell::new(inner),
-            gzip: Cell::new(false),
-            index: None,
-        }
-    }
-
-    fn open_read<R: Read>(&self, path: &Path, read: R) -> io::Result<TarFsFile> {
-        let mut archive = Archive::new(read);
-        for entry in archive.entries()? {
-            let mut entry = entry?;
-            if path == entry.path()? {
-                let mut data = Vec::new();
-                entry.read_to_end(&mut data)?;
-                return Ok(TarFsFile {
-                    inner: Cursor::new(data.into()),
-                });
+            inner: Arc::new(Mutex::new(inner)),
+            compression: OnceLock::new(),
+            cache: Mutex::new(None),
+            index: OnceLock::new(),
+            streaming: false,
+            links: LinkPolicy::default(),
+        }
+    }
+
+    /// Sets how symbolic links are resolved. Defaults to `LinkPolicy::Follow`.
+    ///
+    /// Hard links are always resolved.
+    pub fn link_policy(mut self, policy: LinkPolicy) -> Self {
+        self.links = policy;
+        self
+    }
+
+    /// Sets the compression of the archive, instead of detecting it.
+    ///
+    /// Unless the archive is read with [`streaming`](#method.streaming),
+    /// compressed archives are decompressed whole into memory the first time
+    /// they are read, and the copy is kept for the life of the `TarFs`.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = OnceLock::from(compression);
+        self
+    }
+
+    /// Decompresses opened files as they are read, instead of keeping a
+    /// decompressed copy of the archive in memory. Only the index is kept.
+    ///
+    /// Each file is decompressed from the start of the archive, so opening
+    /// files near the end of a large archive, or seeking backwards in them,
+    /// costs more. Uncompressed archives are always read in place.
+    pub fn streaming(mut self) -> Self {
+        self.streaming = true;
+        self
+    }
+}
+
+impl<T: Read + Seek> TarFs<T> {
+    // Looks up the regular file in the given path. Returns None if there is
+    // something else in the path, or if it has to be resolved.
+    fn lookup(&self, path: &Path) -> io::Result<Option<TarEntry>> {
+        let entry = self.indexed()?.get(path).cloned();
+        Ok(entry.filter(|entry| entry.link.is_none()))
+    }
+
+    // Resolves the symbolic links of a path. Returns the resolved path, along
+    // with the entry in it.
+    fn resolve(&self, path: &Path) -> io::Result<(PathBuf, Option<TarEntry>)> {
+        let idx = self.indexed()?;
+        let real = idx.resolve(path, self.links, TarEntry::symlink)?;
+        let entry = idx.get(&real).cloned();
+        Ok((real, entry))
+    }
+
+    // Calls `f` with a reader positioned at the start of the tar stream.
+    fn scan<R, F>(&self, f: F) -> io::Result<R>
+    where
+        F: Fn(&mut dyn Read) -> io::Result<R>,
+    {
+        match self.source()? {
+            Source::Raw(source) => {
+                let mut source = source.lock().unwrap();
+                source.seek(SeekFrom::Start(0))?;
+                f(&mut *source)
+            }
+            Source::Decompressed(source) => {
+                let mut source = source.lock().unwrap();
+                source.seek(SeekFrom::Start(0))?;
+                f(&mut *source)
+            }
+            Source::Streamed(source, compression) => {
+                let mut source = source.lock().unwrap();
+                source.seek(SeekFrom::Start(0))?;
+                f(&mut compression.decoder(&mut *source)?)
             }
         }
-        Err(io::Error::from(ErrorKind::NotFound))
+    }
+
+    // Returns the compression of the archive, detecting it the first time.
+    fn detect(&self) -> io::Result<Compression> {
+        if let Some(compression) = self.compression.get() {
+            return Ok(*compression);
+        }
+        let mut file = self.inner.lock().unwrap();
+        file.seek(SeekFrom::Start(0))?;
+        let mut magic = Vec::with_capacity(6);
+        (&mut *file).take(6).read_to_end(&mut magic)?;
+        Ok(*self.compression.get_or_init(|| Compression::detect(&magic)))
+    }
+
+    // Returns the reader of the (decompressed) tar stream.
+    // Compressed archives are only decompressed the first time, unless they
+    // are streamed.
+    fn source(&self) -> io::Result<Source<T>> {
+        let compression = self.detect()?;
+        if compression == Compression::None {
+            return Ok(Source::Raw(Arc::clone(&self.inner)));
+        }
+        if self.streaming {
+            return Ok(Source::Streamed(Arc::clone(&self.inner), compression));
+        }
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(ref cache) = *cache {
+            return Ok(Source::Decompressed(Arc::clone(cache)));
+        }
+        let mut file = self.inner.lock().unwrap();
+        file.seek(SeekFrom::Start(0))?;
+        let data = compression.decode(&mut *file)?;
+        let shared = Arc::new(Mutex::new(Cursor::new(data)));
+        *cache = Some(Arc::clone(&shared));
+        Ok(Source::Decompressed(shared))
     }
 
     /// Index the contents of the archive.
     ///
-    /// Having an index allows you to list the contents of the archive using the
-    /// entries_path and entries methods.
+    /// Indexed entries are opened by seeking to their data instead of scanning
+    /// the whole archive. The archive is indexed automatically the first time
+    /// a file is opened or a directory is listed; this method does it upfront.
     pub fn index(self) -> io::Result<Self> {
-        unimplemented!("TarFs indexing hasn't been implemented yet.");
+        self.indexed()?;
+        Ok(self)
+    }
+
+    // Returns the index, building it the first time.
+    fn indexed(&self) -> io::Result<&Index<TarEntry>> {
+        if let Some(index) = self.index.get() {
+            return Ok(index);
+        }
+        let index = self.scan(|read| index_read(read))?;
+        Ok(self.index.get_or_init(|| index))
+    }
+}
+
+// Builds the index in a single pass over the archive.
+// Offsets point to the data of each entry in the (decompressed) tar stream.
+fn index_read<R: Read>(read: R) -> io::Result<Index<TarEntry>> {
+    let mut index = Index::<TarEntry>::new();
+    let mut archive = Archive::new(read);
+    for entry in archive.entries()? {
+        let entry = entry?;
+        // directories are kept even if they are empty
+        if entry.header().entry_type().is_dir() {
+            index.insert_dir(entry.path()?);
+            continue;
+        }
+        let mut tar_entry = TarEntry::new(&entry)?;
+        // hard links share the data of an earlier entry
+        if let Some(TarLink::Hard(ref target)) = tar_entry.link {
+            if let Some(target) = index.get(target) {
+                tar_entry = target.clone();
+            }
+        }
+        index.insert(entry.path()?.into_owned(), tar_entry);
     }
+    Ok(index)
 }
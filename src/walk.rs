@@ -0,0 +1,167 @@
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::ffi::OsStr;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::vec;
+
+use crate::store::{Entry, EntryKind, Store};
+
+type SortFn<'a> = Box<dyn FnMut(&Entry, &Entry) -> Ordering + 'a>;
+type FilterFn<'a> = Box<dyn FnMut(&Path, &Entry) -> bool + 'a>;
+
+// Listing of a directory being walked.
+struct Dir {
+    path: PathBuf,
+    depth: usize,
+    entries: vec::IntoIter<io::Result<Entry>>,
+}
+
+/// Recursive directory iterator.
+///
+/// Yields the path (relative to the store) of every file and directory under
+/// the walked directory, along with its entry. Directories are yielded before
+/// their contents.
+///
+/// Errors reading a directory or any of its entries are yielded in place of
+/// the entry, and the walk carries on with the rest of the tree.
+///
+/// Returned by [`StoreExt::walk`].
+///
+/// [`StoreExt::walk`]: ./trait.StoreExt.html#method.walk
+pub struct Walk<'a, S: ?Sized> {
+    store: &'a S,
+    dirs: VecDeque<Dir>,
+    // Directory yielded last, read on the next call to next.
+    pending: Option<(PathBuf, usize)>,
+    breadth_first: bool,
+    max_depth: Option<usize>,
+    sort: Option<SortFn<'a>>,
+    filter: Option<FilterFn<'a>>,
+}
+
+impl<'a, S: Store + ?Sized> Walk<'a, S> {
+    pub(crate) fn new(store: &'a S, path: PathBuf) -> Self {
+        Self {
+            store,
+            dirs: VecDeque::new(),
+            pending: Some((path, 0)),
+            breadth_first: false,
+            max_depth: None,
+            sort: None,
+            filter: None,
+        }
+    }
+
+    /// Visits the tree in breadth-first order, instead of the default
+    /// depth-first order.
+    pub fn breadth_first(mut self) -> Self {
+        self.breadth_first = true;
+        self
+    }
+
+    /// Doesn't descend more than `depth` directories below the walked one.
+    ///
+    /// A depth of 1 yields the entries of the walked directory only.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Sorts the entries of each directory with the given comparator.
+    /// Otherwise, entries are yielded in the order of the store.
+    pub fn sort_by<F>(mut self, cmp: F) -> Self
+    where
+        F: FnMut(&Entry, &Entry) -> Ordering + 'a,
+    {
+        self.sort = Some(Box::new(cmp));
+        self
+    }
+
+    /// Skips the entries for which the predicate returns false. The contents
+    /// of skipped directories are not visited.
+    pub fn filter_entry<F>(mut self, predicate: F) -> Self
+    where
+        F: FnMut(&Path, &Entry) -> bool + 'a,
+    {
+        self.filter = Some(Box::new(predicate));
+        self
+    }
+
+    /// Doesn't descend into the directory yielded last.
+    pub fn skip_current_dir(&mut self) {
+        self.pending = None;
+    }
+
+    // Reads the listing of a directory.
+    fn read_dir(&mut self, path: PathBuf, depth: usize) -> io::Result<()> {
+        let mut entries = self.store.entries_path(&path)?.collect::<Vec<_>>();
+        if let Some(ref mut cmp) = self.sort {
+            // errors go first
+            entries.sort_by(|a, b| match (a, b) {
+                (Ok(a), Ok(b)) => cmp(a, b),
+                (Err(_), Ok(_)) => Ordering::Less,
+                (Ok(_), Err(_)) => Ordering::Greater,
+                (Err(_), Err(_)) => Ordering::Equal,
+            });
+        }
+        self.dirs.push_back(Dir {
+            path,
+            depth,
+            entries: entries.into_iter(),
+        });
+        Ok(())
+    }
+}
+
+// LocalFs names the entries with their path relative to the root of the
+// store, so only the last component is joined to the path of the directory.
+pub(crate) fn file_name(entry: &Entry) -> &OsStr {
+    Path::new(&entry.name).file_name().unwrap_or(&entry.name)
+}
+
+impl<S: Store + ?Sized> Iterator for Walk<'_, S> {
+    type Item = io::Result<(PathBuf, Entry)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((path, depth)) = self.pending.take() {
+                if let Err(e) = self.read_dir(path, depth) {
+                    return Some(Err(e));
+                }
+            }
+
+            // depth-first uses the list of directories as a stack, and
+            // breadth-first as a queue.
+            let dir = if self.breadth_first {
+                self.dirs.front_mut()?
+            } else {
+                self.dirs.back_mut()?
+            };
+            let entry = match dir.entries.next() {
+                Some(Ok(entry)) => entry,
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    if self.breadth_first {
+                        self.dirs.pop_front();
+                    } else {
+                        self.dirs.pop_back();
+                    }
+                    continue;
+                }
+            };
+            let path = dir.path.join(file_name(&entry));
+            let depth = dir.depth + 1;
+
+            if let Some(ref mut filter) = self.filter {
+                if !filter(&path, &entry) {
+                    continue;
+                }
+            }
+            if entry.kind == EntryKind::Dir && self.max_depth.is_none_or(|max| depth < max) {
+                self.pending = Some((path.clone(), depth));
+            }
+            return Some(Ok((path, entry)));
+        }
+    }
+}
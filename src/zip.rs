@@ -1,13 +1,20 @@
-use std::cell::RefCell;
+use std::convert::TryFrom;
 use std::fs;
-use std::io::{self, Cursor, ErrorKind, Read, Seek, SeekFrom};
-use std::path::Path;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use zip_::ZipArchive;
+use flate2::read::DeflateDecoder;
+use flate2::Crc;
+use zip_::read::ZipFile;
+use zip_::result::ZipError;
+use zip_::{CompressionMethod, DateTime, ZipArchive};
 
 use crate::index::Index;
-use crate::store::Store;
-use crate::{Entries, Entry};
+use crate::section::{seek_position, ReadSeek, Section};
+use crate::store::{self, Store};
+use crate::{Entries, Entry, EntryKind, File, Glob, LinkPolicy, Metadata, Pattern};
 
 /// Zip archive store.
 ///
@@ -15,27 +22,245 @@ use crate::{Entries, Entry};
 ///
 /// When used with a `std::fs::File`, the file will remain open for the lifetime
 /// of the Zip.
+///
+/// Opened files are decompressed lazily as they are read. Use
+/// [`buffered`](#method.buffered) to decompress small files into memory
+/// instead.
+///
+/// Entries with the Unix mode of a symbolic link are treated as symbolic links
+/// to the path in their contents. Resolving them indexes the archive.
 pub struct ZipFs<T: Read + Seek> {
-    inner: RefCell<T>,
-    index: Option<Index<()>>,
+    inner: Arc<Mutex<T>>,
+    // central directory, parsed the first time it's needed
+    archive: Mutex<Option<ZipArchive<SharedFile<T>>>>,
+    index: OnceLock<Index<ZipEntry>>,
+    buffered: u64,
+    links: LinkPolicy,
+}
+
+/// Metadata of an entry of the archive, along with the target of the
+/// symbolic links.
+#[derive(Clone)]
+struct ZipEntry {
+    meta: Metadata,
+    link: Option<PathBuf>,
+}
+
+impl ZipEntry {
+    fn symlink(&self) -> Option<&Path> {
+        self.link.as_deref()
+    }
+}
+
+/// Handle to the reader of the archive, shared with the opened files.
+///
+/// It keeps its own position, since the opened files move the shared reader
+/// between the reads of the archive.
+struct SharedFile<T> {
+    inner: Arc<Mutex<T>>,
+    pos: u64,
+}
+
+impl<T: Read + Seek> Read for SharedFile<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.seek(SeekFrom::Start(self.pos))?;
+        let n = inner.read(buf)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<T: Seek> Seek for SharedFile<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = match pos {
+            SeekFrom::End(_) => self.inner.lock().unwrap().seek(pos)?,
+            _ => seek_position(self.pos, 0, pos)?,
+        };
+        Ok(self.pos)
+    }
 }
 
 /// Entry in the Zip archive.
-pub struct ZipFsFile {
-    inner: Cursor<Box<[u8]>>,
+///
+/// Files of archives over any reader are converted into a [`File`] of the
+/// `Zip` variant, which hides the type of the reader.
+///
+/// [`File`]: ../enum.File.html
+pub struct ZipFsFile<T: ?Sized = dyn ReadSeek + Send> {
+    inner: ZipFsFileInner<T>,
+}
+
+enum ZipFsFileInner<T: ?Sized> {
+    Buffered(Cursor<Box<[u8]>>),
+    Stored(Checked<Section<T>>),
+    Deflated(Checked<Inflate<T>>),
 }
 
-impl Read for ZipFsFile {
+impl<T: ReadSeek + Send + 'static> From<ZipFsFile<T>> for File {
+    fn from(file: ZipFsFile<T>) -> Self {
+        let inner = match file.inner {
+            ZipFsFileInner::Buffered(file) => ZipFsFileInner::Buffered(file),
+            ZipFsFileInner::Stored(file) => ZipFsFileInner::Stored(file.map(Section::erase)),
+            ZipFsFileInner::Deflated(file) => ZipFsFileInner::Deflated(file.map(Inflate::erase)),
+        };
+        File::Zip(ZipFsFile { inner })
+    }
+}
+
+impl<T: ReadSeek + ?Sized> Read for ZipFsFile<T> {
     #[inline]
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner.read(buf)
+        match self.inner {
+            ZipFsFileInner::Buffered(ref mut file) => file.read(buf),
+            ZipFsFileInner::Stored(ref mut file) => file.read(buf),
+            ZipFsFileInner::Deflated(ref mut file) => file.read(buf),
+        }
     }
 }
 
-impl Seek for ZipFsFile {
+impl<T: ReadSeek + ?Sized> Seek for ZipFsFile<T> {
     #[inline]
     fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
-        self.inner.seek(pos)
+        match self.inner {
+            ZipFsFileInner::Buffered(ref mut file) => file.seek(pos),
+            ZipFsFileInner::Stored(ref mut file) => file.seek(pos),
+            ZipFsFileInner::Deflated(ref mut file) => file.seek(pos),
+        }
+    }
+}
+
+/// Streamed entry that checks its CRC-32 when it is read to the end.
+///
+/// Only the data read in order from the start is checked, so reads that skip
+/// part of the entry are not.
+struct Checked<R> {
+    inner: R,
+    crc: Crc,
+    expected: u32,
+    // uncompressed size of the entry
+    size: u64,
+    // amount of data checked so far
+    checked: u64,
+    // position of the file
+    pos: u64,
+}
+
+impl<R> Checked<R> {
+    fn new(inner: R, size: u64, expected: u32) -> Self {
+        Self {
+            inner,
+            crc: Crc::new(),
+            expected,
+            size,
+            checked: 0,
+            pos: 0,
+        }
+    }
+
+    fn map<S, F: FnOnce(R) -> S>(self, f: F) -> Checked<S> {
+        Checked {
+            inner: f(self.inner),
+            crc: self.crc,
+            expected: self.expected,
+            size: self.size,
+            checked: self.checked,
+            pos: self.pos,
+        }
+    }
+}
+
+impl<R: Read> Read for Checked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if self.pos == self.checked {
+            self.crc.update(&buf[..n]);
+            self.checked += n as u64;
+        }
+        self.pos += n as u64;
+        let eof = n == 0 && !buf.is_empty();
+        if eof && self.checked == self.size && self.crc.sum() != self.expected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid checksum",
+            ));
+        }
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for Checked<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = self.inner.seek(pos)?;
+        Ok(self.pos)
+    }
+}
+
+/// Lazily inflated deflate stream.
+///
+/// Seeking forward inflates and discards the skipped data. Seeking backwards
+/// starts inflating again from the beginning of the entry.
+struct Inflate<T: ?Sized = dyn ReadSeek + Send> {
+    decoder: DeflateDecoder<Section<T>>,
+    // uncompressed size of the entry
+    size: u64,
+    // position of the decoder in the uncompressed stream
+    offset: u64,
+    // position of the file
+    pos: u64,
+}
+
+impl<T: ReadSeek + ?Sized> Inflate<T> {
+    fn new(data: Section<T>, size: u64) -> Self {
+        Self {
+            decoder: DeflateDecoder::new(data),
+            size,
+            offset: 0,
+            pos: 0,
+        }
+    }
+}
+
+impl<T: ReadSeek + Send + 'static> Inflate<T> {
+    fn erase(self) -> Inflate {
+        // the decoder can't change the type of its reader, so it starts over
+        // from the same position
+        let data = self.decoder.get_ref().rewind().erase();
+        Inflate {
+            decoder: DeflateDecoder::new(data),
+            size: self.size,
+            offset: 0,
+            pos: self.pos,
+        }
+    }
+}
+
+impl<T: ReadSeek + ?Sized> Read for Inflate<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos < self.offset {
+            let data = self.decoder.get_ref().rewind();
+            self.decoder = DeflateDecoder::new(data);
+            self.offset = 0;
+        }
+        if self.pos > self.offset {
+            let skip = self.pos - self.offset;
+            let skipped = io::copy(&mut (&mut self.decoder).take(skip), &mut io::sink())?;
+            self.offset += skipped;
+            if skipped < skip {
+                return Ok(0);
+            }
+        }
+        let n = self.decoder.read(buf)?;
+        self.offset += n as u64;
+        self.pos = self.offset;
+        Ok(n)
+    }
+}
+
+impl<T: ?Sized> Seek for Inflate<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = seek_position(self.pos, self.size, pos)?;
+        Ok(self.pos)
     }
 }
 
@@ -53,61 +278,276 @@ impl ZipFs<fs::File> {
 impl<T: Read + Seek> ZipFs<T> {
     pub fn new(inner: T) -> Self {
         Self {
-            inner: RefCell::new(inner),
-            index: None,
+            inner: Arc::new(Mutex::new(inner)),
+            archive: Mutex::new(None),
+            index: OnceLock::new(),
+            buffered: 0,
+            links: LinkPolicy::default(),
         }
     }
 
+    /// Sets how symbolic links are resolved. Defaults to `LinkPolicy::Follow`.
+    pub fn link_policy(mut self, policy: LinkPolicy) -> Self {
+        self.links = policy;
+        self
+    }
+
+    /// Decompress files of up to `size` bytes into memory when they are
+    /// opened, instead of streaming them from the archive.
+    ///
+    /// Buffered files have their CRC checked as they are read.
+    pub fn buffered(mut self, size: u64) -> Self {
+        self.buffered = size;
+        self
+    }
+
     /// Index the contents of the archive.
     ///
-    /// Having an index allows you to list the contents of the archive using the
-    /// entries_path and entries methods.
-    pub fn index(mut self) -> io::Result<Self> {
-        let mut index = Index::new();
-        let mut file = self.inner.borrow_mut();
-        file.seek(SeekFrom::Start(0))?;
-        let mut archive = ZipArchive::new(&mut *file)?;
-        for i in 0..archive.len() {
-            let file = archive.by_index(i)?;
-            let path = file.mangled_name();
-
-            index.insert(path, ());
-        }
-        self.index = Some(index);
-        drop(file);
+    /// Listing the contents of the archive using the entries_path and entries
+    /// methods indexes it automatically. Call this method to pay the cost
+    /// upfront.
+    pub fn index(self) -> io::Result<Self> {
+        self.indexed()?;
         Ok(self)
     }
+
+    // Returns the index, building it the first time.
+    fn indexed(&self) -> io::Result<&Index<ZipEntry>> {
+        if let Some(index) = self.index.get() {
+            return Ok(index);
+        }
+        let index = self.with_archive(|archive| {
+            let mut index = Index::new();
+            for i in 0..archive.len() {
+                let mut file = archive.by_index(i)?;
+                let path = file.mangled_name();
+                let meta = metadata(&file);
+                let link = if meta.is_symlink() {
+                    let mut target = String::new();
+                    file.read_to_string(&mut target)?;
+                    Some(PathBuf::from(target))
+                } else {
+                    None
+                };
+
+                index.insert(path, ZipEntry { meta, link });
+            }
+            Ok(index)
+        })?;
+        Ok(self.index.get_or_init(|| index))
+    }
+
+    // Calls `f` with the parsed archive.
+    // The central directory is only parsed the first time.
+    fn with_archive<R, F>(&self, f: F) -> io::Result<R>
+    where
+        F: FnOnce(&mut ZipArchive<SharedFile<T>>) -> io::Result<R>,
+    {
+        let mut archive = self.archive.lock().unwrap();
+        if archive.is_none() {
+            let inner = SharedFile {
+                inner: Arc::clone(&self.inner),
+                pos: 0,
+            };
+            *archive = Some(ZipArchive::new(inner)?);
+        }
+        match *archive {
+            Some(ref mut archive) => f(archive),
+            None => unreachable!(),
+        }
+    }
+}
+
+impl<T: Read + Seek> ZipFs<T> {
+    // Opens the file stored at `path`. Returns None if there is no such file,
+    // or if it is a symbolic link.
+    fn open_file(&self, path: &Path) -> io::Result<Option<ZipFsFile<T>>> {
+        let name = utf8(path)?;
+
+        self.with_archive(|archive| {
+            let mut file = match archive.by_name(name) {
+                Ok(file) => file,
+                Err(ZipError::FileNotFound) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+            if metadata(&file).is_symlink() {
+                return Ok(None);
+            }
+            let stream = match file.compression() {
+                _ if file.size() <= self.buffered => None,
+                CompressionMethod::Stored => Some(false),
+                CompressionMethod::Deflated => Some(true),
+                _ => None,
+            };
+            let inner = match stream {
+                None => {
+                    let mut v = Vec::new();
+                    file.read_to_end(&mut v)?;
+                    ZipFsFileInner::Buffered(Cursor::new(v.into()))
+                }
+                Some(deflate) => {
+                    let shared = Arc::clone(&self.inner);
+                    let data = Section::new(shared, file.data_start(), file.compressed_size());
+                    let (size, crc32) = (file.size(), file.crc32());
+                    if deflate {
+                        let data = Inflate::new(data, size);
+                        ZipFsFileInner::Deflated(Checked::new(data, size, crc32))
+                    } else {
+                        ZipFsFileInner::Stored(Checked::new(data, size, crc32))
+                    }
+                }
+            };
+            Ok(Some(ZipFsFile { inner }))
+        })
+    }
+
+    // Resolves the symbolic links of a path. Returns the resolved path, along
+    // with the entry in it.
+    fn resolve(&self, path: &Path) -> io::Result<(PathBuf, Option<ZipEntry>)> {
+        let idx = self.indexed()?;
+        let real = idx.resolve(path, self.links, ZipEntry::symlink)?;
+        let entry = idx.get(&real).cloned();
+        Ok((real, entry))
+    }
 }
 
 impl<T: Read + Seek> Store for ZipFs<T> {
-    type File = ZipFsFile;
+    type File = ZipFsFile<T>;
     fn open_path(&self, path: &Path) -> io::Result<Self::File> {
-        let mut file = self.inner.borrow_mut();
-        file.seek(SeekFrom::Start(0))?;
-
-        let mut archive = ZipArchive::new(&mut *file)?;
-        let name = path.to_str().ok_or(io::Error::new(
-            ErrorKind::Other,
-            "Utf8 path conversion error.",
-        ));
-        let mut file = archive.by_name(name?)?;
-
-        let mut v = Vec::new();
-        file.read_to_end(&mut v)?;
-        Ok(ZipFsFile {
-            inner: Cursor::new(v.into()),
-        })
+        if let Some(file) = self.open_file(path)? {
+            return Ok(file);
+        }
+        match self.resolve(path)? {
+            (_, Some(ref entry)) if entry.link.is_some() => Err(store::link_error()),
+            (real, Some(_)) => self
+                .open_file(&real)?
+                .ok_or_else(|| io::ErrorKind::NotFound.into()),
+            (_, None) => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn entries_path(&self, path: &Path) -> io::Result<Entries<'_>> {
+        let idx = self.indexed()?;
+        Ok(Entries::new(idx.entries(path).map(|ent| {
+            let name = ent.name.to_os_string();
+            let kind = ent.meta.map_or(ent.kind, |e| e.meta.kind);
+            Ok(Entry { name, kind })
+        })))
+    }
+
+    fn glob_path(&self, pattern: &Pattern) -> io::Result<Glob<'_>> {
+        Ok(Glob::index(self.indexed()?, pattern, |e| e.meta.kind))
+    }
+
+    fn metadata_path(&self, path: &Path) -> io::Result<Metadata> {
+        if self.index.get().is_none() {
+            let meta = self.with_archive(|archive| match archive.by_name(utf8(path)?) {
+                Ok(file) => Ok(Some(metadata(&file))),
+                Err(ZipError::FileNotFound) => Ok(None),
+                Err(e) => Err(e.into()),
+            })?;
+            match meta {
+                Some(meta) if !meta.is_symlink() => return Ok(meta),
+                _ => {}
+            }
+        }
+        match self.resolve(path)? {
+            (_, Some(entry)) => Ok(entry.meta),
+            (ref real, None) if self.indexed()?.contains_dir(real) => Ok(Metadata::dir()),
+            (_, None) => Err(io::ErrorKind::NotFound.into()),
+        }
     }
 
-    fn entries_path(&self, path: &Path) -> io::Result<Entries> {
-        if let Some(ref idx) = self.index {
-            Ok(Entries::new(idx.entries(path).map(|ent| {
-                let name = ent.name.to_os_string();
-                let kind = ent.kind;
-                Ok(Entry { name, kind })
-            })))
-        } else {
-            panic!("You have to call the `Zip::index` method on this zip archive before you can list its entries.")
+    fn read_link_path(&self, path: &Path) -> io::Result<PathBuf> {
+        let idx = self.indexed()?;
+        let path = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => idx
+                .resolve(parent, self.links, ZipEntry::symlink)?
+                .join(name),
+            _ => path.to_path_buf(),
+        };
+        match idx.get(path).map(ZipEntry::symlink) {
+            Some(Some(target)) => Ok(target.to_path_buf()),
+            Some(None) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "path is not a symbolic link",
+            )),
+            None => Err(io::ErrorKind::NotFound.into()),
         }
     }
 }
+
+fn utf8(path: &Path) -> io::Result<&str> {
+    path.to_str()
+        .ok_or_else(|| io::Error::other("Utf8 path conversion error."))
+}
+
+// Reads the metadata of a file from the central directory.
+fn metadata(file: &ZipFile) -> Metadata {
+    let kind = if file.is_dir() {
+        EntryKind::Dir
+    } else if file.unix_mode().is_some_and(|m| m & 0o170_000 == 0o120_000) {
+        EntryKind::Symlink
+    } else {
+        EntryKind::File
+    };
+    Metadata {
+        kind,
+        len: file.size(),
+        modified: system_time(file.last_modified()),
+        permissions: file.unix_mode().map(|m| m & 0o7777),
+        crc32: Some(file.crc32()),
+    }
+}
+
+// Converts an MS-DOS timestamp (no timezone, taken as UTC) to system time.
+fn system_time(time: DateTime) -> Option<SystemTime> {
+    // days since the epoch of the civil date (Howard Hinnant's algorithm)
+    let (y, m, d) = (
+        i64::from(time.year()),
+        i64::from(time.month()),
+        i64::from(time.day()),
+    );
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    let secs = days * 86_400
+        + i64::from(time.hour()) * 3600
+        + i64::from(time.minute()) * 60
+        + i64::from(time.second());
+    u64::try_from(secs)
+        .ok()
+        .map(|s| UNIX_EPOCH + Duration::from_secs(s))
+}
+
+// Converts system time to an MS-DOS timestamp (taken as UTC). Returns None
+// for times the format can't represent.
+pub(crate) fn dos_time(time: SystemTime) -> Option<DateTime> {
+    let secs = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let (days, secs) = ((secs / 86_400) as i64, secs % 86_400);
+
+    // civil date of the days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + i64::from(m <= 2);
+
+    DateTime::from_date_and_time(
+        u16::try_from(y).ok()?,
+        m as u8,
+        d as u8,
+        (secs / 3600) as u8,
+        (secs / 60 % 60) as u8,
+        (secs % 60) as u8,
+    )
+    .ok()
+}
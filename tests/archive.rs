@@ -0,0 +1,76 @@
+#![cfg(all(feature = "tar", feature = "zip"))]
+use mini_fs::prelude::*;
+use mini_fs::{read_archive, MiniFs};
+use std::io::{Cursor, ErrorKind, Read};
+
+#[test]
+fn archive_formats() {
+    let files: [&'static [u8]; 3] = [
+        include_bytes!("archive2.tar"),
+        include_bytes!("archive2.tar.gz"),
+        include_bytes!("archive2.zip"),
+    ];
+    for file in &files {
+        let archive = read_archive(Cursor::new(*file)).unwrap();
+        let mut hello = String::new();
+        archive
+            .open("nested/hello.txt")
+            .unwrap()
+            .read_to_string(&mut hello)
+            .unwrap();
+        assert_eq!("hello\n", hello);
+        assert!(archive.metadata("nested").unwrap().is_dir());
+    }
+}
+
+#[test]
+fn archive_mount() {
+    let file = include_bytes!("archive2.tar.gz");
+    let archive = read_archive(Cursor::new(&file[..])).unwrap();
+    let fs = MiniFs::new().mount("/mods/archive2", archive);
+    assert!(fs.open("/mods/archive2/nested/world.txt").is_ok());
+}
+
+#[test]
+fn archive_unrecognized() {
+    let file = b"hello, world!\n";
+    let err = read_archive(Cursor::new(&file[..])).err().unwrap();
+    assert_eq!(ErrorKind::InvalidData, err.kind());
+}
+
+#[test]
+fn archive_not_tar() {
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    // compressed streams must contain a tar archive
+    let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+    gz.write_all(b"hello, world!\n").unwrap();
+    let file = gz.finish().unwrap();
+    let err = read_archive(Cursor::new(file)).err().unwrap();
+    assert_eq!(ErrorKind::InvalidData, err.kind());
+}
+
+#[test]
+fn archive_zip_prefix() {
+    // self-extracting archives start with an executable
+    let mut file = b"#!/bin/sh\nexit 1\n".to_vec();
+    file.extend_from_slice(include_bytes!("archive2.zip"));
+    let archive = read_archive(Cursor::new(file)).unwrap();
+    let mut hello = String::new();
+    archive
+        .open("nested/hello.txt")
+        .unwrap()
+        .read_to_string(&mut hello)
+        .unwrap();
+    assert_eq!("hello\n", hello);
+}
+
+#[test]
+#[cfg(feature = "zstd")]
+fn archive_zst() {
+    let file = include_bytes!("archive2.tar.zst");
+    let archive = read_archive(Cursor::new(&file[..])).unwrap();
+    assert!(archive.open("nested/hello.txt").is_ok());
+}
@@ -3,6 +3,9 @@ use std::io::prelude::*;
 use mini_fs::prelude::*;
 use mini_fs::{CaselessFs, RamFs};
 
+mod common;
+use common::Counted;
+
 #[cfg(test)]
 #[test]
 fn caseless() {
@@ -50,10 +53,187 @@ fn caseless() {
     let mut txt = String::new();
     let mut file = caseless.open("/A.tXt").unwrap();
     file.read_to_string(&mut txt).unwrap();
-    assert_eq!(["low a", "high a"].iter().any(|s| s == &txt), true);
+    assert!(["low a", "high a"].iter().any(|s| s == &txt));
 
     let mut txt = String::new();
     let mut file = caseless.open("/b/B.tXt").unwrap();
     file.read_to_string(&mut txt).unwrap();
-    assert_eq!(["low b", "high b"].iter().any(|s| s == &txt), true);
+    assert!(["low b", "high b"].iter().any(|s| s == &txt));
+}
+
+#[test]
+fn caseless_unicode() {
+    use mini_fs::caseless::{Folding, Normalization};
+
+    let mut ram = RamFs::new();
+    ram.touch("/Ä.png", b"a".to_vec());
+    ram.touch("/straße.txt", b"b".to_vec());
+    ram.touch("/ΣΟΦΟΣ/e\u{301}te\u{301}.txt", b"c".to_vec());
+
+    let caseless = CaselessFs::new(ram);
+    assert!(caseless.open("/ä.png").is_ok());
+    assert!(caseless.open("/STRASSE.TXT").is_ok());
+    assert!(caseless.open("/σοφος/e\u{301}te\u{301}.txt").is_ok());
+    // decomposed names need normalization
+    assert!(caseless.open("/σοφος/\u{e9}t\u{e9}.txt").is_err());
+
+    let caseless = caseless.normalization(Normalization::Nfc);
+    assert!(caseless.open("/σοφος/\u{c9}T\u{c9}.txt").is_ok());
+
+    let caseless = caseless.folding(Folding::Ascii);
+    assert!(caseless.open("/ä.png").is_err());
+    assert!(caseless.open("/ΣΟΦΟΣ/\u{e9}T\u{e9}.TXT").is_ok());
+}
+
+#[test]
+fn caseless_turkic() {
+    use mini_fs::caseless::Folding;
+
+    let mut ram = RamFs::new();
+    ram.touch("/İstanbul.txt", b"a".to_vec());
+    ram.touch("/ırmak.txt", b"b".to_vec());
+
+    let caseless = CaselessFs::new(ram);
+    assert!(caseless.open("/istanbul.txt").is_err());
+    assert!(caseless.open("/IRMAK.TXT").is_err());
+
+    let caseless = caseless.folding(Folding::Turkic);
+    assert!(caseless.open("/istanbul.txt").is_ok());
+    assert!(caseless.open("/IRMAK.txt").is_ok());
+    assert!(caseless.open("/irmak.txt").is_err());
+}
+
+#[test]
+fn caseless_cached() {
+    let mut ram = RamFs::new();
+    ram.touch("Textures/Walls/Brick.png", b"brick".to_vec());
+    let counted = Counted {
+        inner: ram,
+        listed: Default::default(),
+    };
+
+    let caseless = CaselessFs::new(counted).cached();
+    assert!(caseless.open("textures/walls/brick.png").is_ok());
+    assert_eq!(3, caseless.get_ref().listed.get());
+    assert!(caseless.open("TEXTURES/WALLS/BRICK.PNG").is_ok());
+    assert!(caseless.open("textures/walls/stone.png").is_err());
+    assert_eq!(3, caseless.get_ref().listed.get());
+
+    // only the directory itself is listed, once
+    assert_eq!(1, caseless.entries("Textures/Walls").unwrap().count());
+    assert_eq!(4, caseless.get_ref().listed.get());
+    let path = std::path::Path::new("./Textures/Walls");
+    assert_eq!(1, caseless.entries_path(path).unwrap().count());
+    assert_eq!(5, caseless.get_ref().listed.get());
+
+    // the new file isn't in the cached table
+    let mut caseless = caseless;
+    let ram = &mut caseless.get_mut().inner;
+    ram.touch("Textures/Walls/Stone.png", b"stone".to_vec());
+    assert!(caseless.open("textures/walls/stone.png").is_ok());
+
+    caseless
+        .get_ref()
+        .inner
+        .write("Textures/Walls/Wood.png", "wood")
+        .unwrap();
+    assert!(caseless.open("textures/walls/wood.png").is_err());
+    caseless.invalidate("Textures/Walls");
+    assert!(caseless.open("textures/walls/wood.png").is_ok());
+}
+
+#[test]
+fn caseless_index() {
+    let mut ram = RamFs::new();
+    ram.touch("Textures/Walls/Brick.png", b"brick".to_vec());
+    let counted = Counted {
+        inner: ram,
+        listed: Default::default(),
+    };
+
+    let caseless = CaselessFs::new(counted).index().unwrap();
+    let listed = caseless.get_ref().listed.get();
+    assert!(caseless.open("textures/walls/brick.png").is_ok());
+    assert_eq!(listed, caseless.get_ref().listed.get());
+}
+
+#[test]
+fn caseless_strict() {
+    use mini_fs::caseless::Ambiguous;
+    use std::path::PathBuf;
+
+    let mut ram = RamFs::new();
+    ram.touch("Readme.txt", b"a".to_vec());
+    ram.touch("README.txt", b"b".to_vec());
+    ram.touch("docs/Guide.md", b"c".to_vec());
+    ram.touch("docs/sub/x.txt", b"d".to_vec());
+    ram.touch("docs/sub/X.TXT", b"e".to_vec());
+
+    let caseless = CaselessFs::new(ram).strict();
+    assert!(caseless.open("Readme.txt").is_ok());
+    assert!(caseless.open("docs/guide.MD").is_ok());
+
+    let err = caseless.open("readme.txt").err().unwrap();
+    let ambiguous = err.get_ref().unwrap().downcast_ref::<Ambiguous>().unwrap();
+    let mut candidates = ambiguous.candidates.clone();
+    candidates.sort();
+    assert_eq!(
+        vec![PathBuf::from("README.txt"), PathBuf::from("Readme.txt")],
+        candidates
+    );
+    assert!(caseless.metadata("readme.txt").is_err());
+
+    assert_eq!(
+        vec![
+            vec![PathBuf::from("README.txt"), PathBuf::from("Readme.txt")],
+            vec![
+                PathBuf::from("docs/sub/X.TXT"),
+                PathBuf::from("docs/sub/x.txt")
+            ],
+        ],
+        caseless.collisions("").unwrap()
+    );
+    assert_eq!(1, caseless.collisions("docs").unwrap().len());
+}
+
+#[test]
+fn caseless_entries() {
+    let mut ram = RamFs::new();
+    ram.touch("Textures/a.png", b"a".to_vec());
+    ram.touch("Textures/B.png", b"b".to_vec());
+    ram.touch("textures/b.PNG", b"b".to_vec());
+    ram.touch("textures/c.png", b"c".to_vec());
+    ram.touch("other.txt", b"o".to_vec());
+    ram.touch("Docs/Readme.txt", b"r".to_vec());
+    ram.touch("Docs/README.txt", b"R".to_vec());
+    ram.touch("docs/readme.TXT", b"r".to_vec());
+
+    let caseless = CaselessFs::new(ram);
+    let names = |path: &str| {
+        let mut names = caseless
+            .entries(path)
+            .unwrap()
+            .map(|e| e.unwrap().name.into_string().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+    let lower = |names: Vec<String>| {
+        let mut names = names.iter().map(|n| n.to_lowercase()).collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+    assert_eq!(vec!["a.png", "b.png", "c.png"], lower(names("TEXTURES")));
+    // the real path goes first
+    assert_eq!(vec!["a.png", "b.PNG", "c.png"], names("textures"));
+    assert_eq!(vec!["B.png", "a.png", "c.png"], names("Textures"));
+    // names in the same directory are never merged
+    assert_eq!(
+        vec!["Docs", "Textures", "docs", "other.txt", "textures"],
+        names("")
+    );
+    assert_eq!(vec!["README.txt", "Readme.txt"], names("Docs"));
+    assert_eq!(vec!["readme.TXT"], names("docs"));
+    assert!(caseless.metadata("TEXTURES/C.PNG").unwrap().is_file());
+    assert!(caseless.metadata("TEXTURES").unwrap().is_dir());
 }
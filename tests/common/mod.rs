@@ -0,0 +1,63 @@
+// Helpers shared by the integration tests. Each test crate uses a different
+// subset of them.
+#![allow(dead_code)]
+
+use mini_fs::prelude::*;
+use mini_fs::{Entries, RamFs};
+use std::cell::Cell;
+use std::io::{ErrorKind, Read, Result};
+use std::path::Path;
+
+/// Reads a whole file of the store into a string.
+pub fn read<S: Store>(store: &S, path: &str) -> String
+where
+    S::File: Read,
+{
+    let mut content = String::new();
+    store
+        .open(path)
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    content
+}
+
+/// Builds a `RamFs` with the given files and contents.
+pub fn ram(files: &[(&str, &str)]) -> RamFs {
+    let mut ram = RamFs::new();
+    for (path, content) in files {
+        ram.touch(*path, content.as_bytes().to_vec());
+    }
+    ram
+}
+
+/// Store without any files that doesn't implement listing, so listing it
+/// always fails with `Unsupported`.
+pub struct Unlisted;
+
+impl Store for Unlisted {
+    type File = mini_fs::File;
+
+    fn open_path(&self, _: &Path) -> Result<Self::File> {
+        Err(ErrorKind::NotFound.into())
+    }
+}
+
+/// Store that counts the directories it lists.
+pub struct Counted<S> {
+    pub inner: S,
+    pub listed: Cell<usize>,
+}
+
+impl<S: Store> Store for Counted<S> {
+    type File = S::File;
+
+    fn open_path(&self, path: &Path) -> Result<Self::File> {
+        self.inner.open_path(path)
+    }
+
+    fn entries_path(&self, path: &Path) -> Result<Entries<'_>> {
+        self.listed.set(self.listed.get() + 1);
+        self.inner.entries_path(path)
+    }
+}
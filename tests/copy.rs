@@ -0,0 +1,94 @@
+use mini_fs::copy::{self, Conflict, Options, Progress};
+use mini_fs::prelude::*;
+use mini_fs::{Entries, Entry, EntryKind, RamFs};
+use std::io::{ErrorKind, Result};
+use std::path::Path;
+
+mod common;
+use common::{ram, read};
+
+const FILES: &[(&str, &str)] = &[
+    ("dlc/levels/1.map", "level 1"),
+    ("dlc/levels/bonus/2.map", "level 2"),
+    ("dlc/readme.txt", "readme"),
+];
+
+#[test]
+fn copy_tree() {
+    let dst = RamFs::new();
+    let mut copied = Vec::new();
+    let options = Options::new().progress(|path, _| copied.push(path.to_path_buf()));
+    let progress = copy::copy_tree(&ram(FILES), "dlc/levels", &dst, "out/levels", options).unwrap();
+    assert_eq!(
+        Progress {
+            files: 2,
+            bytes: 14,
+            skipped: 0
+        },
+        progress
+    );
+    assert_eq!(2, copied.len());
+    assert_eq!("level 1", read(&dst, "out/levels/1.map"));
+    assert_eq!("level 2", read(&dst, "out/levels/bonus/2.map"));
+    assert!(dst.open("out/readme.txt").is_err());
+}
+
+#[test]
+fn copy_conflicts() {
+    let dst = RamFs::new();
+    dst.write("levels/1.map", "old").unwrap();
+
+    let err =
+        copy::copy_tree(&ram(FILES), "dlc/levels", &dst, "levels", Options::new()).unwrap_err();
+    assert_eq!(ErrorKind::AlreadyExists, err.kind());
+
+    let options = Options::new().conflict(Conflict::Skip);
+    let progress = copy::copy_tree(&ram(FILES), "dlc/levels", &dst, "levels", options).unwrap();
+    assert_eq!((1, 1), (progress.files, progress.skipped));
+    assert_eq!("old", read(&dst, "levels/1.map"));
+
+    let options = Options::new().conflict(Conflict::Overwrite);
+    let progress = copy::copy_tree(&ram(FILES), "dlc/levels", &dst, "levels", options).unwrap();
+    assert_eq!((2, 0), (progress.files, progress.skipped));
+    assert_eq!("level 1", read(&dst, "levels/1.map"));
+}
+
+#[test]
+#[cfg(feature = "zip")]
+fn copy_from_zip() {
+    use mini_fs::{LocalFs, ZipFs};
+    use std::{env, fs};
+
+    let file = include_bytes!("archive2.zip");
+    let zip = ZipFs::new(std::io::Cursor::new(&file[..]));
+    let root = env::temp_dir().join(format!("mini-fs-copy-{}", std::process::id()));
+    let local = LocalFs::new(&root);
+    copy::copy_tree(&zip, "", &local, "archive2", Options::new()).unwrap();
+    assert_eq!("hello\n", read(&local, "archive2/nested/hello.txt"));
+    fs::remove_dir_all(&root).unwrap();
+}
+
+// Store with an entry that tries to escape the copied directory.
+struct Escape;
+
+impl Store for Escape {
+    type File = mini_fs::File;
+
+    fn open_path(&self, _: &Path) -> Result<Self::File> {
+        Err(ErrorKind::NotFound.into())
+    }
+
+    fn entries_path(&self, _: &Path) -> Result<Entries<'_>> {
+        Ok(Entries::new(vec![Ok(Entry {
+            name: "..".into(),
+            kind: EntryKind::File,
+        })]))
+    }
+}
+
+#[test]
+fn copy_escape() {
+    let dst = RamFs::new();
+    let err = copy::copy_tree(&Escape, "dir", &dst, "out", Options::new()).unwrap_err();
+    assert_eq!(ErrorKind::PermissionDenied, err.kind());
+}
@@ -1,9 +1,12 @@
 use mini_fs::prelude::*;
-use mini_fs::{EntryKind, LocalFs, MiniFs, RamFs};
+use mini_fs::{Entries, EntryKind, LocalFs, MiniFs, RamFs};
 use std::collections::BTreeMap;
 use std::ffi::OsStr;
 use std::io::Result;
 
+mod common;
+use common::Unlisted;
+
 // https://github.com/germangb/mini-fs/issues/6
 #[test]
 fn ram_fs_entries_kind() {
@@ -57,7 +60,7 @@ fn overriden_entries_0() {
 
     let files = MiniFs::new().mount("/f0", f0).mount("/f0", f1);
 
-    let mut entries = files
+    let entries = files
         .entries("/f0")
         .unwrap()
         .collect::<Result<Vec<_>>>()
@@ -73,7 +76,7 @@ fn overriden_entries_1() {
 
     let files = MiniFs::new().mount("/f0", f0).mount("/f0", f1);
 
-    let mut entries = files
+    let entries = files
         .entries("/f0")
         .unwrap()
         .collect::<Result<Vec<_>>>()
@@ -99,7 +102,6 @@ fn tuple_no_repeats() {
 
 #[test]
 fn local_trait_object_entries() {
-    use mini_fs::prelude::*;
     use mini_fs::{LocalFs, Store};
     use std::path::Path;
 
@@ -154,3 +156,73 @@ fn local_entries() {
         entries.next().unwrap().map(|e| e.name).unwrap()
     );
 }
+
+#[test]
+fn unsupported_entries() {
+    use std::io::ErrorKind;
+
+    let kind = |res: Result<Entries<'_>>| res.err().map(|e| e.kind());
+
+    assert_eq!(Some(ErrorKind::Unsupported), kind(Unlisted.entries("/")));
+
+    let files = MiniFs::new().mount("/files", Unlisted);
+    assert_eq!(Some(ErrorKind::Unsupported), kind(files.entries("/files")));
+    assert_eq!(
+        Some(ErrorKind::NotFound),
+        kind(
+            MiniFs::new()
+                .mount("/local", LocalFs::new("./tests/local"))
+                .entries("/local/nope")
+        )
+    );
+}
+
+#[test]
+fn mount_point_entries() {
+    let names = |fs: &MiniFs, path: &str| {
+        fs.entries(path)
+            .unwrap()
+            .map(|e| e.map(|e| (e.name.into_string().unwrap(), e.kind)))
+            .collect::<Result<Vec<_>>>()
+            .unwrap()
+    };
+
+    let mut root = RamFs::new();
+    root.touch("a.txt", b"a".to_vec());
+    root.touch("assets/b.txt", b"b".to_vec());
+
+    let fs = MiniFs::new()
+        .mount("/assets/gfx", LocalFs::new("./tests/local"))
+        .mount("/assets/sfx", RamFs::new());
+    assert_eq!(
+        vec![("assets".to_string(), EntryKind::Dir)],
+        names(&fs, "/")
+    );
+    assert_eq!(
+        vec![
+            ("gfx".to_string(), EntryKind::Dir),
+            ("sfx".to_string(), EntryKind::Dir)
+        ],
+        names(&fs, "/assets")
+    );
+    assert_eq!(3, names(&fs, "/assets/gfx").len());
+    assert!(fs.metadata("/assets").unwrap().is_dir());
+
+    let fs = MiniFs::new()
+        .mount("/", root)
+        .mount("/assets/gfx", LocalFs::new("./tests/local"));
+    assert_eq!(
+        vec![
+            ("assets".to_string(), EntryKind::Dir),
+            ("a.txt".to_string(), EntryKind::File)
+        ],
+        names(&fs, "/")
+    );
+    assert_eq!(
+        vec![
+            ("gfx".to_string(), EntryKind::Dir),
+            ("b.txt".to_string(), EntryKind::File)
+        ],
+        names(&fs, "/assets")
+    );
+}
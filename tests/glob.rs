@@ -0,0 +1,123 @@
+use mini_fs::prelude::*;
+use mini_fs::{LocalFs, MiniFs, Pattern};
+use std::io::{ErrorKind, Result};
+
+mod common;
+use common::{ram, Unlisted};
+
+const FILES: &[(&str, &str)] = &[
+    ("a.png", "a"),
+    ("gfx/b.png", "b"),
+    ("gfx/ui/c.png", "c"),
+    ("gfx/ui/d.gif", "d"),
+    ("sfx/e.wav", "e"),
+];
+
+fn paths<S: Store>(store: &S, pattern: &str) -> Vec<String> {
+    let mut paths = store
+        .glob(pattern)
+        .unwrap()
+        .map(|e| e.map(|(path, _)| path.to_str().unwrap().to_string()))
+        .collect::<Result<Vec<_>>>()
+        .unwrap();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn glob_pattern() {
+    let pattern = Pattern::new("/assets/**/*.{png,gif}").unwrap();
+    assert!(pattern.matches("/assets/a.png"));
+    assert!(pattern.matches("/assets/x/y/b.gif"));
+    assert!(!pattern.matches("/assets/c.wav"));
+    assert!(!pattern.matches("/other/a.png"));
+
+    let pattern = Pattern::new("file?.[a-c0-9]").unwrap();
+    assert!(pattern.matches("file1.b"));
+    assert!(pattern.matches("file_.7"));
+    assert!(!pattern.matches("file1.d"));
+    assert!(!pattern.matches("file12.b"));
+
+    let pattern = Pattern::new("[!.]*").unwrap();
+    assert!(pattern.matches("visible"));
+    assert!(!pattern.matches(".hidden"));
+
+    let pattern = Pattern::new("{a/{b,c},d}/*").unwrap();
+    assert!(pattern.matches("a/b/x"));
+    assert!(pattern.matches("a/c/x"));
+    assert!(pattern.matches("d/x"));
+    assert!(!pattern.matches("a/x"));
+
+    assert!(Pattern::new(r"\*").unwrap().matches("*"));
+    assert!(!Pattern::new(r"\*").unwrap().matches("a"));
+
+    for invalid in &["{a,b", "[ab", r"a\"] {
+        let err = Pattern::new(invalid).unwrap_err();
+        assert_eq!(ErrorKind::InvalidInput, err.kind());
+    }
+}
+
+#[test]
+fn glob_ram() {
+    let ram = ram(FILES);
+
+    assert_eq!(
+        vec!["a.png", "gfx/b.png", "gfx/ui/c.png"],
+        paths(&ram, "**/*.png")
+    );
+    assert_eq!(vec!["gfx/b.png"], paths(&ram, "gfx/*.png"));
+    assert_eq!(vec!["gfx", "sfx"], paths(&ram, "?fx"));
+    assert_eq!(
+        vec!["gfx/b.png", "gfx/ui", "gfx/ui/c.png", "gfx/ui/d.gif"],
+        paths(&ram, "gfx/**")
+    );
+    assert!(paths(&ram, "nope/**").is_empty());
+}
+
+#[test]
+fn glob_mini_fs() {
+    let fs = MiniFs::new()
+        .mount("/assets", ram(FILES))
+        .mount("/local", LocalFs::new("./tests/local"))
+        .mount("/unlisted", Unlisted);
+
+    // listing always fails, so stores that are pruned never get listed
+    assert_eq!(
+        vec!["/assets/a.png", "/assets/gfx/b.png", "/assets/gfx/ui/c.png"],
+        paths(&fs, "/{assets,local}/**/*.png")
+    );
+    assert_eq!(vec!["/local/baz/foobar"], paths(&fs, "/local/**/foo?ar"));
+    assert!(fs.glob("/**").unwrap().any(|e| e.is_err()));
+}
+
+#[test]
+#[cfg(feature = "zip")]
+fn glob_zip() {
+    use mini_fs::ZipFs;
+    use std::io::Cursor;
+
+    let file = include_bytes!("archive2.zip");
+    let zip = ZipFs::new(Cursor::new(&file[..]));
+
+    assert_eq!(
+        vec!["hello.txt", "nested/hello.txt"],
+        paths(&zip, "**/hello.txt")
+    );
+    assert_eq!(vec!["nested/world.txt"], paths(&zip, "nested/w*"));
+}
+
+#[test]
+#[cfg(feature = "tar")]
+fn glob_tar() {
+    use mini_fs::TarFs;
+    use std::io::Cursor;
+
+    let file = include_bytes!("archive2.tar.gz");
+    let tar = TarFs::new(Cursor::new(&file[..]));
+
+    assert_eq!(
+        vec!["hello.txt", "nested/hello.txt"],
+        paths(&tar, "**/hello.txt")
+    );
+    assert_eq!(vec!["nested/world.txt"], paths(&tar, "nested/w*"));
+}
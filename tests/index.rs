@@ -103,3 +103,35 @@ fn test_normal_path() {
         normalize_path(Path::new("/a/b/c/.././../../"))
     );
 }
+
+#[test]
+fn index_remove() {
+    let mut index = Index::new();
+
+    index.insert("a/b/c.txt", 1);
+    index.insert("a/b/d.txt", 2);
+    index.insert("a/e.txt", 4);
+    index.insert_dir("f/g");
+
+    assert_eq!(None, index.remove("a/b"));
+    assert_eq!(None, index.remove("nope"));
+    assert_eq!(Some(1), index.remove("a/b/c.txt"));
+    assert_eq!(None, index.get("a/b/c.txt"));
+    assert!(index.contains_dir("a/b"));
+
+    // implied directories are pruned once empty
+    assert_eq!(Some(2), index.remove("a/b/d.txt"));
+    assert!(!index.contains_dir("a/b"));
+    assert!(index.contains_dir("a"));
+    assert_eq!(Some(4), index.remove("a/e.txt"));
+    assert!(!index.contains_dir("a"));
+
+    // explicit directories are not
+    index.insert("f/g/h.txt", 8);
+    assert_eq!(Some(8), index.remove("f/g/h.txt"));
+    assert!(index.contains_dir("f/g"));
+
+    assert!(index.remove_dir_all("f"));
+    assert!(!index.remove_dir_all("f"));
+    assert_eq!(0, index.entries(".").count());
+}
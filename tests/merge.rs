@@ -1,3 +1,5 @@
+mod common;
+
 #[test]
 fn merge_tup() {
     use mini_fs::prelude::*;
@@ -53,3 +55,199 @@ fn merge_vec() {
 
     assert_eq!("overriden", atxt);
 }
+
+#[test]
+fn merge_union_mount() {
+    use mini_fs::prelude::*;
+    use mini_fs::{MiniFs, RamFs};
+    use std::io::prelude::*;
+    use std::io::ErrorKind;
+
+    let mut a = RamFs::new();
+    let mut b = RamFs::new();
+
+    a.touch("a.txt", String::from("a.txt").into_bytes());
+    a.touch("b.txt", String::from("b.txt").into_bytes());
+    b.touch("a.txt", String::from("overriden").into_bytes());
+    b.touch("c.txt", String::from("c.txt").into_bytes());
+
+    let fs: MiniFs = MiniFs::new().mount("/files", a).mount("/files", b).union();
+
+    assert!(fs.open("/files/b.txt").is_ok());
+    assert!(fs.open("/files/c.txt").is_ok());
+    assert!(fs.metadata("/files/b.txt").unwrap().is_file());
+    assert_eq!(
+        ErrorKind::NotFound,
+        fs.open("/files/d.txt").err().unwrap().kind()
+    );
+
+    let mut atxt = String::new();
+    let mut file = fs.open("/files/a.txt").unwrap();
+    file.read_to_string(&mut atxt).unwrap();
+    assert_eq!("overriden", atxt);
+
+    let mut names = fs
+        .entries("/files")
+        .unwrap()
+        .map(|e| e.unwrap().name.into_string().unwrap())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(vec!["a.txt", "b.txt", "c.txt"], names);
+
+    // without union, the last mount hides the first one
+    let mut a = RamFs::new();
+    a.touch("a.txt", String::from("a.txt").into_bytes());
+    let fs: MiniFs = MiniFs::new()
+        .mount("/files", a)
+        .mount("/files", RamFs::new());
+    assert!(fs.open("/files/a.txt").is_err());
+    assert_eq!(0, fs.entries("/files").unwrap().count());
+}
+
+fn whiteout_layers() -> (mini_fs::RamFs, mini_fs::RamFs) {
+    use mini_fs::RamFs;
+
+    let mut base = RamFs::new();
+    base.touch("a.txt", b"a".to_vec());
+    base.touch("b.txt", b"b".to_vec());
+    base.touch("levels/1.map", b"1".to_vec());
+    base.touch("levels/2.map", b"2".to_vec());
+    base.touch("music/intro.ogg", b"intro".to_vec());
+    base.touch("music/theme.ogg", b"theme".to_vec());
+
+    let mut patch = RamFs::new();
+    patch.touch("c.txt", b"c".to_vec());
+    patch.touch(".wh.b.txt", Vec::new());
+    patch.touch(".wh.levels", Vec::new());
+    patch.touch("music/.wh..wh..opq", Vec::new());
+    patch.touch("music/theme.ogg", b"new theme".to_vec());
+    (patch, base)
+}
+
+fn assert_whiteouts<S>(fs: &S)
+where
+    S: mini_fs::Store,
+    S::File: std::io::Read,
+{
+    use mini_fs::prelude::*;
+    use std::io::Read;
+
+    let names = |path: &str| {
+        let mut names = fs
+            .entries(path)
+            .unwrap()
+            .map(|e| e.unwrap().name.into_string().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+
+    assert!(fs.open("a.txt").is_ok());
+    assert!(fs.open("c.txt").is_ok());
+    assert!(fs.open("b.txt").is_err());
+    assert!(fs.metadata("b.txt").is_err());
+    assert!(fs.open(".wh.b.txt").is_err());
+    assert_eq!(vec!["a.txt", "c.txt", "music"], names(""));
+
+    // whiteouts hide whole directories
+    assert!(fs.open("levels/1.map").is_err());
+    assert!(fs.metadata("levels").is_err());
+
+    // opaque directories only show the upper contents
+    assert!(fs.open("music/intro.ogg").is_err());
+    assert!(fs.open("music/.wh..wh..opq").is_err());
+    let mut theme = String::new();
+    fs.open("music/theme.ogg")
+        .unwrap()
+        .read_to_string(&mut theme)
+        .unwrap();
+    assert_eq!("new theme", theme);
+    assert_eq!(vec!["theme.ogg"], names("music"));
+}
+
+#[test]
+fn merge_tup_whiteout() {
+    let (patch, base) = whiteout_layers();
+    assert_whiteouts(&(patch, base));
+}
+
+#[test]
+fn merge_vec_whiteout() {
+    use mini_fs::prelude::*;
+    use mini_fs::RamFs;
+
+    let (patch, base) = whiteout_layers();
+    let fs = vec![patch, base];
+    assert_whiteouts(&fs);
+
+    // whiteouts only hide the layers below
+    let mut top = RamFs::new();
+    top.touch("b.txt", b"restored".to_vec());
+    let (patch, base) = whiteout_layers();
+    let fs = vec![top, patch, base];
+    assert!(fs.open("b.txt").is_ok());
+}
+
+#[test]
+fn merge_tup_local_whiteout() {
+    use mini_fs::prelude::*;
+    use mini_fs::{LocalFs, RamFs};
+    use std::path::Path;
+    use std::{env, fs};
+
+    let root = env::temp_dir().join(format!("mini-fs-whiteout-{}", std::process::id()));
+    fs::create_dir_all(root.join("dir")).unwrap();
+    fs::write(root.join("dir/a.txt"), "a").unwrap();
+    // the contents of the markers don't matter
+    fs::write(root.join("dir/.wh.b.txt"), "hidden").unwrap();
+
+    let mut base = RamFs::new();
+    base.touch("dir/a.txt", b"a".to_vec());
+    base.touch("dir/b.txt", b"b".to_vec());
+    base.touch("dir/c.txt", b"c".to_vec());
+
+    // the layers name their entries differently, but list them once
+    let overlay = (LocalFs::new(&root), base);
+    let mut names = overlay
+        .entries("dir")
+        .unwrap()
+        .map(|e| e.unwrap().name)
+        .map(|n| {
+            Path::new(&n)
+                .file_name()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(vec!["a.txt", "c.txt"], names);
+    assert!(overlay.open("dir/b.txt").is_err());
+    assert!(overlay.open("dir/c.txt").is_ok());
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn merge_whiteout_listings() {
+    use common::Counted;
+    use mini_fs::prelude::*;
+
+    let (mut patch, base) = whiteout_layers();
+    patch.touch("docs/readme.txt", b"readme".to_vec());
+    let patch = Counted {
+        inner: patch,
+        listed: Default::default(),
+    };
+    let fs = (patch, base);
+
+    // markers are taken from the listing itself, and opaque directories
+    // don't need their parents
+    assert_eq!(1, fs.entries("music").unwrap().count());
+    assert_eq!(1, fs.0.listed.get());
+
+    // otherwise each parent is listed once to look for whiteouts
+    assert_eq!(1, fs.entries("docs").unwrap().count());
+    assert_eq!(3, fs.0.listed.get());
+}
@@ -0,0 +1,101 @@
+use mini_fs::prelude::*;
+use mini_fs::{EntryKind, LocalFs, MiniFs, RamFs};
+use std::io::Cursor;
+use std::time::{Duration, UNIX_EPOCH};
+
+#[test]
+fn local_metadata() {
+    let local = LocalFs::new("./tests");
+
+    let meta = local.metadata("archive.tar").unwrap();
+    assert_eq!(EntryKind::File, meta.kind);
+    assert_eq!(10240, meta.len);
+    assert!(meta.modified.is_some());
+
+    let meta = local.metadata("local/baz").unwrap();
+    assert!(meta.is_dir());
+    assert_eq!(0, meta.len);
+
+    assert!(local.metadata("nope").is_err());
+}
+
+#[test]
+fn ram_metadata() {
+    let mut ram = RamFs::new();
+    ram.touch("/a/b.txt", b"hello".to_vec());
+
+    let meta = ram.metadata("/a/b.txt").unwrap();
+    assert!(meta.is_file());
+    assert_eq!(5, meta.len);
+
+    assert!(ram.metadata("/a").unwrap().is_dir());
+    assert!(ram.metadata("/").unwrap().is_dir());
+    assert!(ram.metadata("/a/nope.txt").is_err());
+}
+
+#[test]
+#[cfg(feature = "zip")]
+fn zip_metadata() {
+    use mini_fs::ZipFs;
+
+    let file = include_bytes!("lines.zip");
+    let mtime = UNIX_EPOCH + Duration::from_secs(1_557_581_460);
+
+    let unindexed = ZipFs::new(Cursor::new(&file[..]));
+    let indexed = ZipFs::new(Cursor::new(&file[..])).index().unwrap();
+    for zip in &[unindexed, indexed] {
+        let meta = zip.metadata("lines.txt").unwrap();
+        assert!(meta.is_file());
+        assert_eq!(22528, meta.len);
+        assert_eq!(Some(0xf276_3942), meta.crc32);
+        assert_eq!(Some(mtime), meta.modified);
+        assert_eq!(Some(0o644), meta.permissions);
+
+        assert!(zip.metadata("stored").unwrap().is_dir());
+        assert!(zip.metadata("nope").is_err());
+    }
+}
+
+#[test]
+#[cfg(feature = "tar")]
+fn tar_metadata() {
+    use mini_fs::TarFs;
+
+    let tar = include_bytes!("archive2.tar");
+    let tar_gz = include_bytes!("archive2.tar.gz");
+    let mtime = UNIX_EPOCH + Duration::from_secs(1_557_581_460);
+
+    for file in &[&tar[..], &tar_gz[..]] {
+        let unindexed = TarFs::new(Cursor::new(*file));
+        let indexed = TarFs::new(Cursor::new(*file)).index().unwrap();
+        for tar in &[unindexed, indexed] {
+            let meta = tar.metadata("nested/world.txt").unwrap();
+            assert!(meta.is_file());
+            assert_eq!(7, meta.len);
+            assert_eq!(Some(mtime), meta.modified);
+            assert_eq!(Some(0o644), meta.permissions);
+
+            assert!(tar.metadata("nested").unwrap().is_dir());
+            assert!(tar.metadata("nope").is_err());
+        }
+    }
+}
+
+#[test]
+fn mini_fs_metadata() {
+    let mut a = RamFs::new();
+    let mut b = RamFs::new();
+    a.touch("a.txt", b"a".to_vec());
+    b.touch("a.txt", b"overriden".to_vec());
+    b.touch("b.txt", b"b".to_vec());
+
+    let fs = MiniFs::new()
+        .mount("/local", LocalFs::new("./tests/local"))
+        .mount("/files", (b, a));
+
+    assert!(fs.metadata("/local/baz").unwrap().is_dir());
+    assert_eq!(9, fs.metadata("/files/a.txt").unwrap().len);
+    assert_eq!(1, fs.metadata("/files/b.txt").unwrap().len);
+    assert!(fs.metadata("/files/c.txt").is_err());
+    assert!(fs.metadata("/nope").is_err());
+}
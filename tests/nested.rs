@@ -0,0 +1,74 @@
+#![cfg(all(feature = "tar", feature = "zip"))]
+use mini_fs::prelude::*;
+use mini_fs::{EntryKind, MiniFs, NestedFs, TarFs};
+use std::io::Cursor;
+
+mod common;
+use common::read;
+
+fn packs() -> NestedFs<TarFs<Cursor<&'static [u8]>>> {
+    let file = include_bytes!("packs.tar");
+    NestedFs::new(TarFs::new(Cursor::new(&file[..])))
+}
+
+#[test]
+fn nested_open() {
+    let fs = packs();
+    assert_eq!("packs\n", read(&fs, "packs/readme.txt"));
+    assert_eq!("hello\n", read(&fs, "packs/dlc1.zip/textures/a.txt"));
+    assert_eq!("world!\n", read(&fs, "packs/dlc1.zip/inner.tar.gz/b.txt"));
+    assert_eq!("not a zip\n", read(&fs, "packs/fake.zip"));
+    assert!(fs.open("packs/dlc1.zip/nope.txt").is_err());
+    assert!(fs.open("packs/fake.zip/a.txt").is_err());
+    // the archive file itself
+    assert!(fs.open("packs/dlc1.zip").is_ok());
+
+    let fs = MiniFs::new().mount("/content", packs());
+    assert_eq!(
+        "world!\n",
+        read(&fs, "/content/packs/dlc1.zip/inner.tar.gz/b.txt")
+    );
+}
+
+#[test]
+fn nested_entries() {
+    let fs = packs();
+    let mut entries = fs
+        .entries("packs")
+        .unwrap()
+        .map(|e| e.unwrap())
+        .map(|e| (e.name.into_string().unwrap(), e.kind))
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        vec![
+            ("dlc1.zip".to_string(), EntryKind::Dir),
+            ("fake.zip".to_string(), EntryKind::File),
+            ("readme.txt".to_string(), EntryKind::File),
+        ],
+        entries
+    );
+    assert!(fs.metadata("packs/dlc1.zip").unwrap().is_dir());
+    assert!(fs
+        .metadata("packs/dlc1.zip/textures/a.txt")
+        .unwrap()
+        .is_file());
+    assert!(fs.metadata("packs/fake.zip").unwrap().is_file());
+
+    let paths = fs
+        .walk("packs/dlc1.zip")
+        .map(|e| e.unwrap().0.to_str().unwrap().to_string())
+        .filter(|p| p.ends_with(".txt"))
+        .count();
+    assert_eq!(2, paths);
+
+    // files that are not archives are walked as files
+    let files = fs
+        .walk("packs")
+        .map(|e| e.unwrap())
+        .filter(|(_, entry)| entry.kind == EntryKind::File)
+        .map(|(path, _)| path.to_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert!(files.iter().any(|p| p == "packs/fake.zip"));
+    assert!(files.iter().any(|p| p.ends_with("inner.tar.gz/b.txt")));
+}
@@ -0,0 +1,106 @@
+#![cfg(any(feature = "tar", feature = "zip"))]
+use mini_fs::pack;
+use mini_fs::prelude::*;
+
+mod common;
+use common::{ram, read};
+
+const FILES: &[(&str, &str)] = &[
+    ("gfx/a.png", "a"),
+    ("gfx/b.psd", "b"),
+    ("gfx/ui/c.png", "c"),
+    ("sfx/d.ogg", "d"),
+];
+
+#[test]
+#[cfg(feature = "zip")]
+fn pack_zip() {
+    use mini_fs::ZipFs;
+    use std::io::Cursor;
+
+    for options in [pack::Options::new(), pack::Options::new().stored()] {
+        let zip = pack::to_zip(&ram(FILES), "", Cursor::new(Vec::new()), options).unwrap();
+        let zip = ZipFs::new(Cursor::new(zip.into_inner()));
+        assert_eq!("a", read(&zip, "gfx/a.png"));
+        assert_eq!("c", read(&zip, "gfx/ui/c.png"));
+        assert_eq!("d", read(&zip, "sfx/d.ogg"));
+        assert!(zip.metadata("gfx/ui").unwrap().is_dir());
+    }
+}
+
+#[test]
+#[cfg(feature = "tar")]
+fn pack_tar() {
+    use mini_fs::TarFs;
+    use std::io::Cursor;
+
+    for options in [pack::Options::new(), pack::Options::new().gzip(9)] {
+        let tar = pack::to_tar(&ram(FILES), "gfx", Vec::new(), options).unwrap();
+        let tar = TarFs::new(Cursor::new(tar));
+        assert_eq!("a", read(&tar, "a.png"));
+        assert_eq!("c", read(&tar, "ui/c.png"));
+        assert!(tar.open("sfx/d.ogg").is_err());
+        assert_eq!(3, tar.entries("").unwrap().count());
+    }
+}
+
+#[test]
+#[cfg(feature = "tar")]
+fn pack_filter() {
+    use mini_fs::{EntryKind, TarFs};
+    use std::io::Cursor;
+    use std::path::Path;
+
+    let options = pack::Options::new().filter(|path, entry| {
+        entry.kind == EntryKind::Dir && path != Path::new("gfx/ui")
+            || path.extension() == Some("png".as_ref())
+    });
+    let tar = pack::to_tar(&ram(FILES), "", Vec::new(), options).unwrap();
+    let tar = TarFs::new(Cursor::new(tar));
+    assert!(tar.open("gfx/a.png").is_ok());
+    assert!(tar.open("gfx/b.psd").is_err());
+    assert!(tar.open("gfx/ui/c.png").is_err());
+    assert!(tar.open("sfx/d.ogg").is_err());
+}
+
+#[test]
+#[cfg(all(unix, feature = "tar", feature = "zip"))]
+fn pack_metadata() {
+    use mini_fs::{LocalFs, TarFs, ZipFs};
+    use std::io::Cursor;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    use std::time::Duration;
+    use std::{env, fs};
+
+    let root = env::temp_dir().join(format!("mini-fs-pack-{}", std::process::id()));
+    fs::create_dir_all(root.join("dir")).unwrap();
+    fs::write(root.join("dir/a.sh"), "a").unwrap();
+    fs::set_permissions(root.join("dir/a.sh"), fs::Permissions::from_mode(0o750)).unwrap();
+    symlink("dir/a.sh", root.join("link.sh")).unwrap();
+    let local = LocalFs::new(&root);
+    let modified = local.metadata("dir/a.sh").unwrap().modified.unwrap();
+
+    let tar = pack::to_tar(&local, "", Vec::new(), pack::Options::new()).unwrap();
+    let tar = TarFs::new(Cursor::new(tar));
+    let meta = tar.metadata("dir/a.sh").unwrap();
+    assert_eq!(Some(0o750), meta.permissions);
+    let diff = modified.duration_since(meta.modified.unwrap()).unwrap();
+    assert!(diff < Duration::from_secs(1));
+    assert_eq!(
+        "dir/a.sh",
+        tar.read_link("link.sh").unwrap().to_str().unwrap()
+    );
+    assert_eq!("a", read(&tar, "link.sh"));
+
+    let zip = pack::to_zip(&local, "", Cursor::new(Vec::new()), pack::Options::new()).unwrap();
+    let zip = ZipFs::new(Cursor::new(zip.into_inner()));
+    let meta = zip.metadata("dir/a.sh").unwrap();
+    assert_eq!(Some(0o100_750), meta.permissions.map(|m| m | 0o100_000));
+    let diff = modified.duration_since(meta.modified.unwrap()).unwrap();
+    assert!(diff < Duration::from_secs(2));
+    // links are copied
+    assert!(zip.metadata("link.sh").unwrap().is_file());
+    assert_eq!("a", read(&zip, "link.sh"));
+
+    fs::remove_dir_all(&root).unwrap();
+}
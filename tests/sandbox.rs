@@ -0,0 +1,91 @@
+use mini_fs::prelude::*;
+use mini_fs::LocalFs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::{env, fs};
+
+#[test]
+fn sandboxed() {
+    let base = env::temp_dir().join(format!("mini-fs-sandbox-{}", std::process::id()));
+    let root = base.join("root");
+    fs::create_dir_all(root.join("dir")).unwrap();
+    fs::write(root.join("dir/a.txt"), "a").unwrap();
+    fs::write(base.join("secret.txt"), "secret").unwrap();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::symlink;
+        symlink(base.join("secret.txt"), root.join("link.txt")).unwrap();
+        symlink(&base, root.join("escape")).unwrap();
+        symlink(root.join("dir/a.txt"), root.join("inside.txt")).unwrap();
+    }
+
+    let local = LocalFs::sandboxed(&root).unwrap();
+    // `open_path` skips the path normalization done by `open`.
+    let denied = |path: &str| local.open_path(Path::new(path)).err().map(|e| e.kind());
+
+    assert!(local.open("dir/a.txt").is_ok());
+    assert!(local.open("./dir/a.txt").is_ok());
+    assert_eq!(Some(ErrorKind::PermissionDenied), denied("../secret.txt"));
+    assert_eq!(
+        Some(ErrorKind::PermissionDenied),
+        denied("dir/../../secret.txt")
+    );
+    assert_eq!(
+        Some(ErrorKind::PermissionDenied),
+        denied(base.join("secret.txt").to_str().unwrap())
+    );
+    assert_eq!(Some(ErrorKind::NotFound), denied("nope.txt"));
+    assert_eq!(Some(ErrorKind::PermissionDenied), denied("/dir/a.txt"));
+
+    #[cfg(unix)]
+    {
+        assert!(local.open("inside.txt").is_ok());
+        assert_eq!(Some(ErrorKind::PermissionDenied), denied("link.txt"));
+        assert_eq!(
+            Some(ErrorKind::PermissionDenied),
+            denied("escape/secret.txt")
+        );
+        assert_eq!(
+            ErrorKind::PermissionDenied,
+            local.write("escape/new.txt", "x").unwrap_err().kind()
+        );
+        assert!(!base.join("new.txt").exists());
+    }
+
+    local.create_dir_all("new/nested").unwrap();
+    local.write("new/nested/b.txt", "b").unwrap();
+    assert!(root.join("new/nested/b.txt").exists());
+
+    fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn sandboxed_links() {
+    use std::os::unix::fs::symlink;
+
+    let root = env::temp_dir().join(format!("mini-fs-sandbox-links-{}", std::process::id()));
+    fs::create_dir_all(root.join("dir")).unwrap();
+    fs::write(root.join("dir/a.txt"), "a").unwrap();
+    symlink(root.join("dir"), root.join("dir_link")).unwrap();
+    symlink(root.join("dir/a.txt"), root.join("a_link.txt")).unwrap();
+
+    let local = LocalFs::sandboxed(&root).unwrap();
+
+    // links are removed or replaced, never their targets
+    assert!(local.remove_dir("dir_link").is_err());
+    local.remove_dir_all("dir_link").unwrap();
+    assert!(fs::symlink_metadata(root.join("dir_link")).is_err());
+    assert!(root.join("dir/a.txt").exists());
+
+    local.write("b.txt", "b").unwrap();
+    local.rename("b.txt", "a_link.txt").unwrap();
+    assert!(!fs::symlink_metadata(root.join("a_link.txt"))
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert_eq!("a", fs::read_to_string(root.join("dir/a.txt")).unwrap());
+
+    fs::remove_dir_all(&root).unwrap();
+}
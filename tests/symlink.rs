@@ -0,0 +1,133 @@
+use mini_fs::prelude::*;
+use mini_fs::{EntryKind, LinkPolicy};
+use std::io::{Cursor, ErrorKind};
+use std::path::Path;
+
+mod common;
+use common::read;
+
+fn kind<S: Store>(store: &S, dir: &str, name: &str) -> EntryKind {
+    store
+        .entries(dir)
+        .unwrap()
+        .map(|e| e.unwrap())
+        .find(|e| Path::new(&e.name).file_name() == Some(name.as_ref()))
+        .unwrap()
+        .kind
+}
+
+#[test]
+#[cfg(unix)]
+fn local_symlink() {
+    use mini_fs::LocalFs;
+    use std::os::unix::fs::symlink;
+    use std::{env, fs};
+
+    let base = env::temp_dir().join(format!("mini-fs-symlink-{}", std::process::id()));
+    let root = base.join("root");
+    fs::create_dir_all(root.join("dir")).unwrap();
+    fs::write(root.join("dir/a.txt"), "hello\n").unwrap();
+    fs::write(base.join("secret.txt"), "secret").unwrap();
+    symlink("dir/a.txt", root.join("link.txt")).unwrap();
+    symlink("../secret.txt", root.join("escape.txt")).unwrap();
+
+    let local = LocalFs::new(&root);
+    assert_eq!("hello\n", read(&local, "link.txt"));
+    assert_eq!("secret", read(&local, "escape.txt"));
+    assert_eq!(EntryKind::Symlink, kind(&local, "", "link.txt"));
+    assert_eq!(EntryKind::File, kind(&local, "dir", "a.txt"));
+    assert!(local.metadata("link.txt").unwrap().is_file());
+    assert_eq!(Path::new("dir/a.txt"), local.read_link("link.txt").unwrap());
+
+    let local = LocalFs::new(&root).link_policy(LinkPolicy::NoFollow);
+    assert_eq!(
+        ErrorKind::InvalidInput,
+        local.open("link.txt").err().unwrap().kind()
+    );
+    assert!(local.metadata("link.txt").unwrap().is_symlink());
+    assert!(local.open("dir/a.txt").is_ok());
+
+    let local = LocalFs::new(&root).link_policy(LinkPolicy::FollowWithin);
+    assert_eq!("hello\n", read(&local, "link.txt"));
+    assert_eq!(
+        ErrorKind::PermissionDenied,
+        local.open("escape.txt").err().unwrap().kind()
+    );
+
+    // entries are named after the root as given, even if it isn't canonical
+    let local = LocalFs::new(base.join("root/dir/..")).link_policy(LinkPolicy::FollowWithin);
+    assert_eq!(EntryKind::File, kind(&local, "dir", "a.txt"));
+    let names = local.walk("").map(|e| e.unwrap().0).collect::<Vec<_>>();
+    assert!(names.contains(&Path::new("dir/a.txt").to_path_buf()));
+
+    fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+#[cfg(feature = "tar")]
+fn tar_symlink() {
+    use mini_fs::TarFs;
+
+    let file = include_bytes!("links.tar");
+    let tar = TarFs::new(Cursor::new(&file[..]));
+    assert_eq!("hello\n", read(&tar, "link.txt"));
+    assert_eq!("hello\n", read(&tar, "hard.txt"));
+    assert_eq!("hello\n", read(&tar, "alias/a.txt"));
+    assert_eq!("hello\n", read(&tar, "absolute.txt"));
+    assert!(tar.metadata("link.txt").unwrap().is_file());
+    assert!(tar.metadata("alias").unwrap().is_dir());
+    assert_eq!(Path::new("dir"), tar.read_link("alias").unwrap());
+    assert_eq!(EntryKind::Symlink, kind(&tar, "", "link.txt"));
+    assert_eq!(EntryKind::File, kind(&tar, "", "hard.txt"));
+    assert_eq!(
+        ErrorKind::NotFound,
+        tar.open("escape.txt").err().unwrap().kind()
+    );
+
+    let tar = TarFs::new(Cursor::new(&file[..])).link_policy(LinkPolicy::NoFollow);
+    assert_eq!(
+        ErrorKind::InvalidInput,
+        tar.open("link.txt").err().unwrap().kind()
+    );
+    assert!(tar.metadata("link.txt").unwrap().is_symlink());
+    assert_eq!("hello\n", read(&tar, "hard.txt"));
+
+    let tar = TarFs::new(Cursor::new(&file[..])).link_policy(LinkPolicy::FollowWithin);
+    assert_eq!("hello\n", read(&tar, "link.txt"));
+    assert_eq!(
+        ErrorKind::PermissionDenied,
+        tar.open("escape.txt").err().unwrap().kind()
+    );
+    assert_eq!(
+        ErrorKind::PermissionDenied,
+        tar.open("absolute.txt").err().unwrap().kind()
+    );
+}
+
+#[test]
+#[cfg(feature = "zip")]
+fn zip_symlink() {
+    use mini_fs::ZipFs;
+
+    let file = include_bytes!("links.zip");
+    let zip = ZipFs::new(Cursor::new(&file[..]));
+    assert_eq!("hello\n", read(&zip, "link.txt"));
+    assert_eq!("hello\n", read(&zip, "alias/a.txt"));
+    assert!(zip.metadata("link.txt").unwrap().is_file());
+    assert!(zip.metadata("alias").unwrap().is_dir());
+    assert_eq!(Path::new("dir/a.txt"), zip.read_link("link.txt").unwrap());
+    assert_eq!(EntryKind::Symlink, kind(&zip, "", "link.txt"));
+
+    let zip = ZipFs::new(Cursor::new(&file[..])).link_policy(LinkPolicy::NoFollow);
+    assert_eq!(
+        ErrorKind::InvalidInput,
+        zip.open("link.txt").err().unwrap().kind()
+    );
+    assert!(zip.metadata("link.txt").unwrap().is_symlink());
+
+    let zip = ZipFs::new(Cursor::new(&file[..])).link_policy(LinkPolicy::FollowWithin);
+    assert_eq!(
+        ErrorKind::PermissionDenied,
+        zip.open("escape.txt").err().unwrap().kind()
+    );
+}
@@ -1,11 +1,10 @@
 use std::io::{Cursor, Read};
-use std::path::Path;
 
 #[test]
 #[cfg(feature = "tar")]
 fn tar() {
     use mini_fs::prelude::*;
-    use mini_fs::{Store, TarFs};
+    use mini_fs::TarFs;
 
     let file = include_bytes!("archive.tar");
     let tar = TarFs::new(Cursor::new(&file[..]));
@@ -48,12 +47,317 @@ fn tar_gz() {
 }
 
 #[test]
-#[ignore] // TODO implement tar index
+#[cfg(feature = "tar")]
+fn tar_gz_streaming() {
+    use mini_fs::prelude::*;
+    use mini_fs::{MiniFs, TarFs};
+    use std::io::{Seek, SeekFrom};
+
+    let file = include_bytes!("archive.tar.gz");
+    let tar = TarFs::new(Cursor::new(&file[..])).streaming();
+    let mut a = tar.open("a.txt").unwrap();
+    let mut b = tar.open("b.txt").unwrap();
+    let mut content = String::new();
+    b.read_to_string(&mut content).unwrap();
+    assert_eq!("world!\n", content);
+
+    // seeking backwards decompresses the archive again
+    content.clear();
+    b.seek(SeekFrom::Start(2)).unwrap();
+    b.read_to_string(&mut content).unwrap();
+    assert_eq!("rld!\n", content);
+
+    content.clear();
+    a.seek(SeekFrom::End(-3)).unwrap();
+    a.read_to_string(&mut content).unwrap();
+    assert_eq!("lo\n", content);
+
+    // files keep their position when they are converted
+    let fs = MiniFs::new().mount("/tar", tar);
+    let mut b = fs.open("/tar/b.txt").unwrap();
+    b.seek(SeekFrom::Start(1)).unwrap();
+    content.clear();
+    b.read_to_string(&mut content).unwrap();
+    assert_eq!("orld!\n", content);
+}
+
+#[test]
 #[cfg(feature = "tar")]
 fn tar_entries() {
     use mini_fs::prelude::*;
     use mini_fs::TarFs;
 
-    let file = include_bytes!("archive.tar.gz");
+    let file = include_bytes!("archive2.tar");
     let tar = TarFs::new(Cursor::new(&file[..])).index().unwrap();
+
+    assert_eq!(2, tar.entries("nested").unwrap().collect::<Vec<_>>().len());
+    assert_eq!(3, tar.entries(".").unwrap().collect::<Vec<_>>().len());
+}
+
+#[test]
+#[cfg(feature = "tar")]
+fn tar_gz_entries() {
+    use mini_fs::prelude::*;
+    use mini_fs::TarFs;
+
+    let file = include_bytes!("archive2.tar.gz");
+    let tar = TarFs::new(Cursor::new(&file[..])).index().unwrap();
+
+    assert_eq!(2, tar.entries("nested").unwrap().collect::<Vec<_>>().len());
+    assert_eq!(3, tar.entries(".").unwrap().collect::<Vec<_>>().len());
+
+    let mut hello = String::new();
+    tar.open("nested/hello.txt")
+        .unwrap()
+        .read_to_string(&mut hello)
+        .unwrap();
+    assert_eq!("hello\n", hello);
+}
+
+#[test]
+#[cfg(feature = "tar")]
+fn tar_indexed_open() {
+    use mini_fs::prelude::*;
+    use mini_fs::TarFs;
+
+    let tar = include_bytes!("archive2.tar");
+    let tar_gz = include_bytes!("archive2.tar.gz");
+    for file in &[&tar[..], &tar_gz[..]] {
+        let tar = TarFs::new(Cursor::new(*file)).index().unwrap();
+        for _ in 0..4 {
+            let mut hello = String::new();
+            let mut world = String::new();
+            tar.open("hello.txt")
+                .unwrap()
+                .read_to_string(&mut hello)
+                .unwrap();
+            tar.open("nested/world.txt")
+                .unwrap()
+                .read_to_string(&mut world)
+                .unwrap();
+
+            assert_eq!("hello\n", hello);
+            assert_eq!("world!\n", world);
+            assert!(tar.open("nope").is_err());
+            assert!(tar.open("nested").is_err());
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "tar")]
+fn tar_lazy_index() {
+    use mini_fs::prelude::*;
+    use mini_fs::TarFs;
+    use std::io::{Seek, SeekFrom};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // Reader that counts the bytes read from it.
+    struct Counted(Cursor<&'static [u8]>, Arc<AtomicUsize>);
+
+    impl Read for Counted {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.0.read(buf)?;
+            self.1.fetch_add(n, Ordering::Relaxed);
+            Ok(n)
+        }
+    }
+
+    impl Seek for Counted {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.0.seek(pos)
+        }
+    }
+
+    let file = include_bytes!("archive2.tar");
+    let read = Arc::new(AtomicUsize::new(0));
+    let tar = TarFs::new(Counted(Cursor::new(&file[..]), Arc::clone(&read)));
+
+    // the first open indexes the archive, later ones only read their data
+    let mut hello = String::new();
+    tar.open("hello.txt")
+        .unwrap()
+        .read_to_string(&mut hello)
+        .unwrap();
+    let indexed = read.load(Ordering::Relaxed);
+    for _ in 0..4 {
+        let mut world = String::new();
+        tar.open("nested/world.txt")
+            .unwrap()
+            .read_to_string(&mut world)
+            .unwrap();
+        assert_eq!("world!\n", world);
+    }
+    assert_eq!(indexed + 4 * "world!\n".len(), read.load(Ordering::Relaxed));
+}
+
+#[test]
+#[cfg(feature = "tar")]
+fn tar_duplicate_entries() {
+    use mini_fs::prelude::*;
+    use mini_fs::TarFs;
+    use tar_::{Builder, Header};
+
+    let mut builder = Builder::new(Vec::new());
+    for data in &[&b"old"[..], &b"new"[..]] {
+        let mut header = Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_cksum();
+        builder.append_data(&mut header, "a.txt", *data).unwrap();
+    }
+    let file = builder.into_inner().unwrap();
+
+    // the last entry wins, as when extracting the archive
+    let tar = TarFs::new(Cursor::new(file));
+    let mut content = String::new();
+    tar.open("a.txt")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!("new", content);
+}
+
+#[test]
+#[cfg(feature = "tar")]
+fn tar_empty_dir() {
+    use mini_fs::prelude::*;
+    use mini_fs::{EntryKind, TarFs};
+    use tar_::{Builder, EntryType, Header};
+
+    let mut builder = Builder::new(Vec::new());
+    let mut header = Header::new_gnu();
+    header.set_entry_type(EntryType::Directory);
+    header.set_size(0);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, "empty/", std::io::empty())
+        .unwrap();
+    let file = builder.into_inner().unwrap();
+
+    let tar = TarFs::new(Cursor::new(file));
+    let entries = tar
+        .entries("")
+        .unwrap()
+        .map(|e| e.unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(1, entries.len());
+    assert_eq!(EntryKind::Dir, entries[0].kind);
+    assert!(tar.metadata("empty").unwrap().is_dir());
+    assert_eq!(0, tar.entries("empty").unwrap().count());
+}
+
+#[test]
+#[cfg(feature = "tar")]
+fn tar_lazy_entries() {
+    use mini_fs::prelude::*;
+    use mini_fs::TarFs;
+
+    let file = include_bytes!("archive2.tar.gz");
+    let tar = TarFs::new(Cursor::new(&file[..]));
+
+    assert_eq!(2, tar.entries("nested").unwrap().collect::<Vec<_>>().len());
+    assert_eq!(3, tar.entries(".").unwrap().collect::<Vec<_>>().len());
+}
+
+#[cfg(feature = "tar")]
+fn assert_archive2(file: &'static [u8], compression: mini_fs::Compression) {
+    use mini_fs::prelude::*;
+    use mini_fs::TarFs;
+
+    assert_eq!(compression, mini_fs::Compression::detect(file));
+    for tar in [
+        TarFs::new(Cursor::new(file)),
+        TarFs::new(Cursor::new(file)).compression(compression),
+        TarFs::new(Cursor::new(file)).streaming(),
+    ] {
+        let mut hello = String::new();
+        tar.open("nested/hello.txt")
+            .unwrap()
+            .read_to_string(&mut hello)
+            .unwrap();
+        assert_eq!("hello\n", hello);
+        assert_eq!(3, tar.entries(".").unwrap().count());
+    }
+}
+
+#[test]
+#[cfg(feature = "tar")]
+fn tar_compression() {
+    use mini_fs::Compression;
+
+    assert_archive2(include_bytes!("archive2.tar"), Compression::None);
+    assert_archive2(include_bytes!("archive2.tar.gz"), Compression::Gzip);
+}
+
+#[test]
+#[cfg(feature = "bzip2")]
+fn tar_bz2() {
+    assert_archive2(
+        include_bytes!("archive2.tar.bz2"),
+        mini_fs::Compression::Bzip2,
+    );
+}
+
+#[test]
+#[cfg(feature = "xz")]
+fn tar_xz() {
+    assert_archive2(include_bytes!("archive2.tar.xz"), mini_fs::Compression::Xz);
+}
+
+#[test]
+#[cfg(feature = "zstd")]
+fn tar_zst() {
+    assert_archive2(
+        include_bytes!("archive2.tar.zst"),
+        mini_fs::Compression::Zstd,
+    );
+}
+
+#[test]
+#[cfg(all(feature = "tar", not(feature = "zstd")))]
+fn tar_zst_disabled() {
+    use mini_fs::prelude::*;
+    use mini_fs::TarFs;
+    use std::io::ErrorKind;
+
+    let file = include_bytes!("archive2.tar.zst");
+    let tar = TarFs::new(Cursor::new(&file[..]));
+    assert_eq!(
+        ErrorKind::Unsupported,
+        tar.open("hello.txt").err().unwrap().kind()
+    );
+}
+
+#[test]
+#[cfg(feature = "tar")]
+fn tar_forced_compression() {
+    use mini_fs::prelude::*;
+    use mini_fs::{Compression, TarFs};
+
+    // a gzipped archive read as raw tar
+    let file = include_bytes!("archive2.tar.gz");
+    let tar = TarFs::new(Cursor::new(&file[..])).compression(Compression::None);
+    assert!(tar.open("hello.txt").is_err());
+}
+
+#[test]
+#[cfg(feature = "tar")]
+fn tar_borrowed() {
+    use mini_fs::prelude::*;
+    use mini_fs::TarFs;
+
+    // archives can borrow their data
+    for file in &[
+        include_bytes!("archive2.tar").to_vec(),
+        include_bytes!("archive2.tar.gz").to_vec(),
+    ] {
+        let tar = TarFs::new(Cursor::new(&file[..]));
+        let mut hello = String::new();
+        tar.open("nested/hello.txt")
+            .unwrap()
+            .read_to_string(&mut hello)
+            .unwrap();
+        assert_eq!("hello\n", hello);
+    }
 }
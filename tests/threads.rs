@@ -0,0 +1,134 @@
+use mini_fs::prelude::*;
+use mini_fs::{File, MiniFs, RamFs};
+use std::io::Read;
+use std::sync::Arc;
+use std::thread;
+
+fn assert_send_sync<T: Send + Sync>() {}
+fn assert_send<T: Send>() {}
+
+#[test]
+fn send_sync() {
+    assert_send_sync::<MiniFs>();
+    assert_send_sync::<RamFs>();
+    assert_send::<File>();
+
+    #[cfg(feature = "zip")]
+    assert_send_sync::<mini_fs::ZipFs<std::fs::File>>();
+    #[cfg(feature = "tar")]
+    assert_send_sync::<mini_fs::TarFs<std::fs::File>>();
+}
+
+#[test]
+fn concurrent_open() {
+    let mut ram = RamFs::new();
+    ram.touch("hello.txt", b"hello\n".to_vec());
+
+    let fs = MiniFs::new().mount("/ram", ram);
+
+    #[cfg(feature = "zip")]
+    let fs = {
+        let file = include_bytes!("archive2.zip");
+        let zip = mini_fs::ZipFs::new(std::io::Cursor::new(&file[..]));
+        fs.mount("/zip", zip)
+    };
+    #[cfg(feature = "tar")]
+    let fs = {
+        let file = include_bytes!("archive2.tar.gz");
+        let tar = mini_fs::TarFs::new(std::io::Cursor::new(&file[..]));
+        fs.mount("/tar", tar)
+    };
+
+    let fs = Arc::new(fs);
+    let threads = (0..8)
+        .map(|_| {
+            let fs = Arc::clone(&fs);
+            thread::spawn(move || {
+                let mut paths = vec!["/ram/hello.txt"];
+                if cfg!(feature = "zip") {
+                    paths.push("/zip/nested/hello.txt");
+                }
+                if cfg!(feature = "tar") {
+                    paths.push("/tar/nested/hello.txt");
+                }
+                for _ in 0..16 {
+                    for path in &paths {
+                        let mut content = String::new();
+                        fs.open(path).unwrap().read_to_string(&mut content).unwrap();
+                        assert_eq!("hello\n", content);
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
+
+#[test]
+#[cfg(feature = "zip")]
+fn concurrent_large_reads() {
+    use mini_fs::ZipFs;
+    use std::io::{Cursor, Write};
+    use zip_::write::FileOptions;
+    use zip_::{CompressionMethod, ZipWriter};
+
+    // large entries, so that reading them takes many reads of the archive
+    let contents = (0..4u32)
+        .map(|n| {
+            let mut seed = n + 1;
+            (0..64 * 1024)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                    (seed >> 16) as u8 % 16
+                })
+                .collect::<Vec<u8>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (n, content) in contents.iter().enumerate() {
+        let method = if n % 2 == 0 {
+            CompressionMethod::Stored
+        } else {
+            CompressionMethod::Deflated
+        };
+        let options = FileOptions::default().compression_method(method);
+        zip.start_file(format!("{}.bin", n), options).unwrap();
+        zip.write_all(content).unwrap();
+    }
+    let zip = zip.finish().unwrap().into_inner();
+
+    let zip = Arc::new(ZipFs::new(Cursor::new(zip)));
+    let contents = Arc::new(contents);
+    let threads = (0..16)
+        .map(|t| {
+            let zip = Arc::clone(&zip);
+            let contents = Arc::clone(&contents);
+            thread::spawn(move || {
+                for i in 0..8 {
+                    let n = (t + i) % 4;
+                    let mut file = zip.open(format!("{}.bin", n)).unwrap();
+                    let mut content = Vec::new();
+                    let mut buf = [0; 64];
+                    loop {
+                        let len = file.read(&mut buf).unwrap();
+                        if len == 0 {
+                            break;
+                        }
+                        content.extend_from_slice(&buf[..len]);
+                        // move the shared reader between the reads of the file
+                        drop(zip.open(format!("{}.bin", (n + 1) % 4)).unwrap());
+                    }
+                    assert!(content == contents[n], "corrupt read of {}.bin", n);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
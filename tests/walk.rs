@@ -0,0 +1,113 @@
+use mini_fs::prelude::*;
+use mini_fs::{Entry, LocalFs, MiniFs, RamFs, Walk};
+use std::io::{ErrorKind, Result};
+use std::path::PathBuf;
+
+mod common;
+use common::ram;
+
+const FILES: &[(&str, &str)] = &[
+    ("a.txt", "a"),
+    ("b/c.txt", "c"),
+    ("b/d/e.txt", "e"),
+    ("f/g.txt", "g"),
+];
+
+fn paths<S: Store>(walk: Walk<'_, S>) -> Vec<String> {
+    walk.sort_by(|a, b| a.name.cmp(&b.name))
+        .map(|e| e.map(|(path, _)| path.to_str().unwrap().to_string()))
+        .collect::<Result<Vec<_>>>()
+        .unwrap()
+}
+
+#[test]
+fn walk_order() {
+    let ram = ram(FILES);
+
+    assert_eq!(
+        vec!["a.txt", "b", "b/c.txt", "b/d", "b/d/e.txt", "f", "f/g.txt"],
+        paths(ram.walk(""))
+    );
+    assert_eq!(
+        vec!["a.txt", "b", "f", "b/c.txt", "b/d", "f/g.txt", "b/d/e.txt"],
+        paths(ram.walk("").breadth_first())
+    );
+    assert_eq!(vec!["b/c.txt", "b/d", "b/d/e.txt"], paths(ram.walk("b")));
+}
+
+#[test]
+fn walk_depth_and_filter() {
+    let ram = ram(FILES);
+
+    assert_eq!(vec!["a.txt", "b", "f"], paths(ram.walk("").max_depth(1)));
+    assert_eq!(
+        vec!["a.txt", "f", "f/g.txt"],
+        paths(ram.walk("").filter_entry(|path, _| !path.starts_with("b")))
+    );
+
+    let mut walk = ram.walk("").sort_by(|a, b| a.name.cmp(&b.name));
+    let mut visited = Vec::new();
+    while let Some(entry) = walk.next() {
+        let (path, entry): (PathBuf, Entry) = entry.unwrap();
+        if entry.name == "b" {
+            walk.skip_current_dir();
+        }
+        visited.push(path);
+    }
+    assert_eq!(4, visited.len());
+}
+
+#[test]
+fn walk_mini_fs() {
+    let mut a = RamFs::new();
+    let mut b = RamFs::new();
+    a.touch("a.txt", b"a".to_vec());
+    b.touch("a.txt", b"overriden".to_vec());
+    b.touch("b.txt", b"b".to_vec());
+
+    let fs = MiniFs::new()
+        .mount("/files", (b, a))
+        .mount("/assets/gfx", LocalFs::new("./tests/local/baz"))
+        .mount("/assets/ram", ram(FILES));
+
+    assert_eq!(
+        vec![
+            "/assets",
+            "/assets/gfx",
+            "/assets/gfx/foobar",
+            "/assets/ram",
+            "/assets/ram/a.txt",
+            "/assets/ram/b",
+            "/assets/ram/b/c.txt",
+            "/assets/ram/b/d",
+            "/assets/ram/b/d/e.txt",
+            "/assets/ram/f",
+            "/assets/ram/f/g.txt",
+            "/files",
+            "/files/a.txt",
+            "/files/b.txt",
+        ],
+        paths(fs.walk("/"))
+    );
+}
+
+#[test]
+fn walk_errors() {
+    let fs = MiniFs::new()
+        .mount("/ram", ram(FILES))
+        .mount("/missing", LocalFs::new("./tests/nope"));
+
+    let mut errors = 0;
+    let mut files = 0;
+    for entry in fs.walk("/") {
+        match entry {
+            Ok(_) => files += 1,
+            Err(e) => {
+                assert_eq!(ErrorKind::NotFound, e.kind());
+                errors += 1;
+            }
+        }
+    }
+    assert_eq!(1, errors);
+    assert_eq!(9, files);
+}
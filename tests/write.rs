@@ -0,0 +1,199 @@
+use mini_fs::prelude::*;
+use mini_fs::{LocalFs, MiniFs, RamFs};
+use std::io::{ErrorKind, Write};
+use std::{env, fs};
+
+mod common;
+use common::read;
+
+#[test]
+fn ram_write() {
+    let ram = RamFs::new();
+
+    ram.write("/a.txt", "a").unwrap();
+    assert_eq!("a", read(&ram, "/a.txt"));
+
+    {
+        let mut file = ram.create("/dir/b.txt").unwrap();
+        file.write_all(b"hello ").unwrap();
+        file.write_all(b"world").unwrap();
+    }
+    assert_eq!("hello world", read(&ram, "/dir/b.txt"));
+
+    ram.rename("/dir", "/other/dir").unwrap();
+    assert!(ram.open("/dir/b.txt").is_err());
+    assert_eq!("hello world", read(&ram, "/other/dir/b.txt"));
+
+    ram.rename("/a.txt", "/other/a.txt").unwrap();
+    assert_eq!("a", read(&ram, "/other/a.txt"));
+
+    ram.create_dir_all("/empty/dir").unwrap();
+    assert!(ram.metadata("/empty/dir").unwrap().is_dir());
+    assert!(ram.create_dir_all("/other/dir/b.txt/c").is_err());
+}
+
+#[test]
+fn local_write() {
+    let root = env::temp_dir().join(format!("mini-fs-write-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    let local = LocalFs::new(&root);
+
+    local.create_dir_all("a/b").unwrap();
+    local.write("a/b/c.txt", "hello").unwrap();
+    assert_eq!("hello", read(&local, "a/b/c.txt"));
+
+    {
+        let mut file = local.create("a/d.txt").unwrap();
+        file.write_all(b"world").unwrap();
+    }
+    local.rename("a/d.txt", "a/b/d.txt").unwrap();
+    assert_eq!("world", read(&local, "a/b/d.txt"));
+
+    local.remove_file("a/b/d.txt").unwrap();
+    assert!(local.open("a/b/d.txt").is_err());
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn mini_fs_write() {
+    let mut ro = RamFs::new();
+    ro.touch("a.txt", b"read only".to_vec());
+
+    let fs = MiniFs::new()
+        .mount_mut("/data", RamFs::new())
+        .mount("/data/ro", ro)
+        .mount_mut("/save", RamFs::new());
+
+    fs.write("/data/a.txt", "data").unwrap();
+    fs.write("/save/a.txt", "save").unwrap();
+    assert_eq!("data", read(&fs, "/data/a.txt"));
+    assert_eq!("save", read(&fs, "/save/a.txt"));
+
+    // writes skip the read-only mount and go to the writable one below it
+    fs.write("/data/ro/b.txt", "b").unwrap();
+    assert_eq!("read only", read(&fs, "/data/ro/a.txt"));
+
+    fs.rename("/data/a.txt", "/data/c.txt").unwrap();
+    assert_eq!("data", read(&fs, "/data/c.txt"));
+    assert!(fs.rename("/data/c.txt", "/save/c.txt").is_err());
+
+    let fs = MiniFs::new().mount("/ro", RamFs::new());
+    assert_eq!(
+        ErrorKind::PermissionDenied,
+        fs.write("/ro/a.txt", "a").unwrap_err().kind()
+    );
+    assert_eq!(
+        ErrorKind::NotFound,
+        fs.write("/nope/a.txt", "a").unwrap_err().kind()
+    );
+}
+
+#[test]
+fn ram_remove() {
+    let mut ram = RamFs::new();
+    ram.touch("/a/b/c.txt", b"c".to_vec());
+    ram.touch("/a/d.txt", b"d".to_vec());
+
+    assert_eq!(Some(&b"c"[..]), ram.rm("/a/b/c.txt").as_deref());
+    assert_eq!(None, ram.rm("/a/b/c.txt"));
+    assert!(ram.metadata("/a/b").is_err());
+
+    ram.remove_file("/a/d.txt").unwrap();
+    assert!(ram.open("/a/d.txt").is_err());
+    assert_eq!(
+        ErrorKind::NotFound,
+        ram.remove_file("/a/d.txt").unwrap_err().kind()
+    );
+    ram.touch("/a/d.txt", b"d".to_vec());
+
+    assert!(ram.remove_dir("/a").is_err());
+    ram.create_dir_all("/a/empty").unwrap();
+    ram.remove_dir("/a/empty").unwrap();
+    assert!(ram.metadata("/a/empty").is_err());
+
+    ram.remove_dir_all("/a").unwrap();
+    assert!(ram.open("/a/d.txt").is_err());
+    assert_eq!(
+        ErrorKind::NotFound,
+        ram.remove_dir_all("/a").unwrap_err().kind()
+    );
+}
+
+#[test]
+fn ram_write_over_file() {
+    let ram = RamFs::new();
+    ram.write("/a", "a").unwrap();
+
+    // files are never replaced by the directories of other files
+    assert_eq!(
+        ErrorKind::AlreadyExists,
+        ram.write("/a/b", "b").unwrap_err().kind()
+    );
+    assert_eq!(
+        ErrorKind::AlreadyExists,
+        ram.create("/a/b/c").err().unwrap().kind()
+    );
+    assert_eq!("a", read(&ram, "/a"));
+}
+
+#[test]
+fn ram_file_mut_drop() {
+    let ram = RamFs::new();
+
+    // removed files are not brought back when dropped
+    let mut file = ram.create("/a.txt").unwrap();
+    file.write_all(b"a").unwrap();
+    ram.remove_file("/a.txt").unwrap();
+    drop(file);
+    assert!(ram.open("/a.txt").is_err());
+
+    // nor are renamed ones
+    let mut file = ram.create("/b.txt").unwrap();
+    file.write_all(b"b").unwrap();
+    file.flush().unwrap();
+    ram.rename("/b.txt", "/c.txt").unwrap();
+    drop(file);
+    assert!(ram.open("/b.txt").is_err());
+    assert_eq!("b", read(&ram, "/c.txt"));
+
+    // and files left untouched don't overwrite newer data
+    let file = ram.create("/d.txt").unwrap();
+    ram.write("/d.txt", "d").unwrap();
+    drop(file);
+    assert_eq!("d", read(&ram, "/d.txt"));
+}
+
+#[test]
+fn ram_rename_errors() {
+    let ram = RamFs::new();
+    ram.write("/f.txt", "f").unwrap();
+    ram.write("/a/1.txt", "1").unwrap();
+    ram.write("/b/2.txt", "2").unwrap();
+    ram.create_dir_all("/d").unwrap();
+
+    // files can't replace directories
+    assert_eq!(
+        ErrorKind::InvalidInput,
+        ram.rename("/f.txt", "/d").unwrap_err().kind()
+    );
+    assert_eq!("f", read(&ram, "/f.txt"));
+    assert!(ram.metadata("/d").unwrap().is_dir());
+
+    // nor can directories replace non-empty ones
+    assert!(ram.rename("/a", "/b").is_err());
+    assert_eq!("1", read(&ram, "/a/1.txt"));
+    assert_eq!("2", read(&ram, "/b/2.txt"));
+
+    // files in the destination path are kept
+    assert_eq!(
+        ErrorKind::AlreadyExists,
+        ram.rename("/a", "/f.txt/a").unwrap_err().kind()
+    );
+    assert_eq!("f", read(&ram, "/f.txt"));
+    assert_eq!("1", read(&ram, "/a/1.txt"));
+
+    // empty directories can be replaced
+    ram.rename("/a", "/d").unwrap();
+    assert_eq!("1", read(&ram, "/d/1.txt"));
+}
@@ -1,5 +1,4 @@
 use std::io::{Cursor, Read};
-use std::path::Path;
 
 #[test]
 #[cfg(feature = "zip")]
@@ -34,3 +33,119 @@ fn zip_entries() {
     assert_eq!(2, zip.entries("nested").unwrap().collect::<Vec<_>>().len());
     assert_eq!(3, zip.entries(".").unwrap().collect::<Vec<_>>().len());
 }
+
+#[test]
+#[cfg(feature = "zip")]
+fn zip_seek() {
+    use mini_fs::prelude::*;
+    use mini_fs::ZipFs;
+    use std::io::{Seek, SeekFrom};
+
+    let lines = (0..2048)
+        .map(|i| format!("line {:05}\n", i))
+        .collect::<String>();
+
+    let file = include_bytes!("lines.zip");
+    let streamed = ZipFs::new(Cursor::new(&file[..]));
+    let buffered = ZipFs::new(Cursor::new(&file[..])).buffered(u64::MAX);
+
+    for zip in &[streamed, buffered] {
+        for path in &["lines.txt", "stored/lines.txt"] {
+            let mut file = zip.open(path).unwrap();
+            let mut content = String::new();
+            file.read_to_string(&mut content).unwrap();
+            assert_eq!(lines, content);
+
+            let mut line = [0; 11];
+            file.seek(SeekFrom::Start(11 * 1000)).unwrap();
+            file.read_exact(&mut line).unwrap();
+            assert_eq!(b"line 01000\n", &line);
+
+            file.seek(SeekFrom::Current(-22)).unwrap();
+            file.read_exact(&mut line).unwrap();
+            assert_eq!(b"line 00999\n", &line);
+
+            file.seek(SeekFrom::End(-11)).unwrap();
+            file.read_exact(&mut line).unwrap();
+            assert_eq!(b"line 02047\n", &line);
+
+            assert!(file.seek(SeekFrom::Current(-100_000)).is_err());
+            file.seek(SeekFrom::End(10)).unwrap();
+            assert_eq!(0, file.read(&mut line).unwrap());
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "zip")]
+fn zip_lazy_entries() {
+    use mini_fs::prelude::*;
+    use mini_fs::ZipFs;
+
+    let file = include_bytes!("archive2.zip");
+    let zip = ZipFs::new(Cursor::new(&file[..]));
+
+    assert_eq!(2, zip.entries("nested").unwrap().collect::<Vec<_>>().len());
+    assert_eq!(3, zip.entries(".").unwrap().collect::<Vec<_>>().len());
+}
+
+#[test]
+#[cfg(feature = "zip")]
+fn zip_borrowed() {
+    use mini_fs::prelude::*;
+    use mini_fs::ZipFs;
+
+    // archives can borrow their data
+    let file = include_bytes!("archive.zip").to_vec();
+    let zip = ZipFs::new(Cursor::new(&file[..]));
+
+    let mut hello = String::new();
+    zip.open("hello.txt")
+        .unwrap()
+        .read_to_string(&mut hello)
+        .unwrap();
+    assert_eq!("hello\n", hello);
+}
+
+#[test]
+#[cfg(feature = "zip")]
+fn zip_checksum() {
+    use mini_fs::prelude::*;
+    use mini_fs::ZipFs;
+    use std::io::{ErrorKind, Write};
+    use zip_::write::FileOptions;
+    use zip_::{CompressionMethod, ZipWriter};
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, method) in &[
+        ("stored.txt", CompressionMethod::Stored),
+        ("deflated.txt", CompressionMethod::Deflated),
+    ] {
+        let options = FileOptions::default().compression_method(*method);
+        zip.start_file(*name, options).unwrap();
+        zip.write_all(b"hello hello hello hello\n").unwrap();
+    }
+    let mut file = zip.finish().unwrap().into_inner();
+
+    // streamed entries are checked when they are read to the end
+    let zip = ZipFs::new(Cursor::new(file.clone()));
+    for name in &["stored.txt", "deflated.txt"] {
+        let mut content = String::new();
+        zip.open(name)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!("hello hello hello hello\n", content);
+    }
+
+    let at = file.windows(5).position(|w| w == b"hello").unwrap();
+    file[at] = b'j';
+    let zip = ZipFs::new(Cursor::new(file));
+    let mut content = String::new();
+    let err = zip
+        .open("stored.txt")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap_err();
+    assert_eq!(ErrorKind::InvalidData, err.kind());
+}